@@ -0,0 +1,68 @@
+use std::{io::{Read, Write}, net::TcpListener, thread};
+
+use bevy::prelude::*;
+use maud::html;
+use bevy_html::{HTMLPlugin, HTMLScene};
+
+// Tiny stub server standing in for a real backend
+fn serve(listener: TcpListener) {
+    for (count, stream) in listener.incoming().enumerate() {
+        let Ok(mut stream) = stream else { continue; };
+        let mut request = [0; 4096];
+        let Ok(len) = stream.read(&mut request) else { continue; };
+        let request = String::from_utf8_lossy(&request[..len]);
+
+        let body = html! {
+            Node id="panel" Style="flex_direction: Column, row_gap: Px(10)" {
+                Text TextStyle="size: 30" { "Served " (count + 1) " requests" }
+                Text TextStyle="size: 20" { (request.lines().next().unwrap_or_default()) }
+                Text TextStyle="size: 20" { (request.lines().last().unwrap_or_default()) }
+            }
+        }.into_string();
+        let _ = write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(), body
+        );
+    }
+}
+
+fn startup(mut html_assets: ResMut<Assets<HTMLScene>>, mut commands: Commands) {
+    // UI camera
+    commands.spawn(Camera2dBundle::default());
+
+    let xs = HTMLScene::from(html! {
+        Node Style="width: Percent(100), height: Percent(100), justify_content: Center, align_items: Center, column_gap: Px(20)" {
+            Node Style="flex_direction: Column, row_gap: Px(10)" {
+                Button XForm BackgroundColor="\"#966\"" Style="padding: All(Px(10))"
+                XTarget="Name(\"panel\")" XGet="\"http://127.0.0.1:8080/panel\"" XOn="Click" {
+                    Text TextStyle="size: 30" XName="\"player\"" { "get" }
+                }
+                Button XForm BackgroundColor="\"#669\"" Style="padding: All(Px(10))"
+                XTarget="Name(\"panel\")" XPost="\"http://127.0.0.1:8080/panel\"" XOn="Click" {
+                    Text TextStyle="size: 30" XName="\"player\"" { "post" }
+                }
+            }
+
+            Node id="panel" {
+                Text TextStyle="size: 30" { "Nothing loaded" }
+            }
+        }
+    });
+
+    commands.spawn_empty()
+        .insert(html_assets.add(xs));
+}
+
+fn main() {
+    let listener = TcpListener::bind("127.0.0.1:8080").expect("Couldn't bind stub server");
+    thread::spawn(move || serve(listener));
+
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(HTMLPlugin)
+
+        .add_systems(Startup, startup)
+
+        .run();
+}
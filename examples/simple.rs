@@ -2,23 +2,6 @@ use bevy::prelude::*;
 use maud::html;
 use bevy_html::{HTMLPlugin, HTMLScene, NamedSystemRegistryExt};
 
-#[derive(Reflect, Component, Default)]
-#[reflect(Component, Default)]
-struct BackgroundColorOnInteract {
-    default: Color,
-    hovered: Color,
-    pressed: Color
-}
-fn hover_background_color(mut to_set: Query<(&Interaction, &BackgroundColorOnInteract, &mut BackgroundColor)>) {
-    for (interact, colors, mut background) in &mut to_set {
-        match interact {
-            Interaction::None => {background.0 = colors.default;},
-            Interaction::Pressed => {background.0 = colors.pressed;},
-            Interaction::Hovered => {background.0 = colors.hovered;},
-        }
-    }
-}
-
 #[derive(Resource, Default)]
 struct Number(i32);
 
@@ -43,11 +26,11 @@ fn startup(num: Res<Number>, mut html_assets: ResMut<Assets<HTMLScene>>, mut com
                 }
 
                 Node Style="flex_direction: Column, row_gap: Px(10)" {
-                    Button BackgroundColorOnInteract="default: \"#966\", hovered: \"#A77\", pressed: \"#855\"" Style="padding: All(Px(10))"
+                    Button BackgroundColor="\"#966\"" BackgroundColor:hover="\"#A77\"" BackgroundColor:pressed="\"#855\"" Style="padding: All(Px(10))"
                     XTarget="Name(\"number\")" XFunction="\"increment\"" XOn="Click" {
                         Text TextStyle="size: 30" { "increment" }
                     }
-                    Button BackgroundColorOnInteract="default: \"#669\", hovered: \"#77A\", pressed: \"#558\"" Style="padding: All(Px(10))"
+                    Button BackgroundColor="\"#669\"" BackgroundColor:hover="\"#77A\"" BackgroundColor:pressed="\"#558\"" Style="padding: All(Px(10))"
                     XTarget="Name(\"number\")" XFunction="\"decrement\"" XOn="Click" {
                         Text TextStyle="size: 30" { "decrement" }
                     }
@@ -88,8 +71,6 @@ fn main() {
         .register_named_system("decrement", decrement)
 
         .add_systems(Startup, startup)
-        .register_type::<BackgroundColorOnInteract>()
-        .add_systems(Update, hover_background_color)
 
         .run();
 }
\ No newline at end of file
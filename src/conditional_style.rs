@@ -0,0 +1,342 @@
+use std::any::TypeId;
+
+use bevy::prelude::*;
+use bevy::ecs::reflect::ReflectComponent;
+use bevy::reflect::{TypeRegistry, TypeRegistration};
+use bevy::ui::UiSystem;
+use bevy::utils::HashMap;
+use bevy::window::PrimaryWindow;
+
+use crate::{HTMLSceneSpawnError, construct_instance, deserialize_instance};
+use crate::focus::{HtmlFocus, Disabled};
+use crate::transition::settled_value;
+
+// Element states that attributes can be conditioned on, e.g. BackgroundColor:hover="\"#A77\"".
+// Later states take precedence when several patch the same component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PseudoClass {
+    Hover, // Also while pressed
+    Focus,
+    Pressed,
+    Disabled
+}
+impl PseudoClass {
+    const ALL: [PseudoClass; 4] = [PseudoClass::Hover, PseudoClass::Focus, PseudoClass::Pressed, PseudoClass::Disabled];
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "hover" => Some(Self::Hover),
+            "focus" => Some(Self::Focus),
+            "pressed" => Some(Self::Pressed),
+            "disabled" => Some(Self::Disabled),
+            _ => None
+        }
+    }
+
+    fn applies(&self, state: &ElementState) -> bool {
+        match self {
+            Self::Hover => matches!(state.interaction, Interaction::Hovered | Interaction::Pressed),
+            Self::Focus => state.focused,
+            Self::Pressed => state.interaction == Interaction::Pressed,
+            Self::Disabled => state.disabled
+        }
+    }
+}
+
+// What pseudo-classes are checked against
+struct ElementState {
+    interaction: Interaction,
+    focused: bool,
+    disabled: bool
+}
+
+// Primary window properties that attributes can be conditioned on, e.g. Style@max-width:800="flex_direction: Column".
+// Sizes are logical pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaFeature {
+    MinWidth(f32),
+    MaxWidth(f32),
+    MinHeight(f32),
+    MaxHeight(f32),
+    MinScale(f32), // Window scale factor, e.g. 2 on high DPI screens
+    MaxScale(f32),
+    Portrait,
+    Landscape
+}
+impl MediaFeature {
+    fn parse(feature: &str) -> Option<Self> {
+        let (name, value) = feature.split_once(':')?;
+        let number = value.parse::<f32>();
+        Some(match name {
+            "min-width" => Self::MinWidth(number.ok()?),
+            "max-width" => Self::MaxWidth(number.ok()?),
+            "min-height" => Self::MinHeight(number.ok()?),
+            "max-height" => Self::MaxHeight(number.ok()?),
+            "min-scale" => Self::MinScale(number.ok()?),
+            "max-scale" => Self::MaxScale(number.ok()?),
+            "orientation" if value == "portrait" => Self::Portrait,
+            "orientation" if value == "landscape" => Self::Landscape,
+            _ => return None
+        })
+    }
+
+    fn applies(&self, window: &Window) -> bool {
+        let (width, height, scale) = (window.width(), window.height(), window.scale_factor() as f32);
+        match *self {
+            Self::MinWidth(min) => width >= min,
+            Self::MaxWidth(max) => width <= max,
+            Self::MinHeight(min) => height >= min,
+            Self::MaxHeight(max) => height <= max,
+            Self::MinScale(min) => scale >= min,
+            Self::MaxScale(max) => scale <= max,
+            Self::Portrait => height > width,
+            Self::Landscape => width >= height
+        }
+    }
+}
+
+// A pseudo-class and media features, which all have to apply
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct StyleCondition {
+    class: Option<PseudoClass>,
+    media: Vec<MediaFeature>
+}
+impl StyleCondition {
+    // Pseudo-classes take precedence over media conditions, and those with more features over fewer
+    fn precedence(&self) -> (usize, usize) {
+        let class = self.class.map_or(0, |class| 1 + PseudoClass::ALL.iter().position(|c| *c == class).unwrap());
+        (class, self.media.len())
+    }
+
+    fn applies(&self, state: &ElementState, window: Option<&Window>) -> bool {
+        self.class.map_or(true, |class| class.applies(state))
+            && (self.media.is_empty() || window.is_some_and(|window| self.media.iter().all(|feature| feature.applies(window))))
+    }
+}
+
+// Splits an attribute like BackgroundColor:focus, Style@min-width:600@max-width:1200 or BackgroundColor:hover@max-width:800
+// into the component's attribute and its condition, leaving generic attributes like Handle:Image alone
+pub(crate) fn split_style_condition(attribute: &str) -> Result<Option<(&str, StyleCondition)>, HTMLSceneSpawnError> {
+    let (component, media) = match attribute.split_once('@') {
+        Some((component, media)) => (component, Some(media)),
+        None => (attribute, None)
+    };
+    let mut condition = StyleCondition::default();
+    let component = match component.rsplit_once(':').and_then(|(component, class)| Some((component, PseudoClass::parse(class)?))) {
+        Some((component, class)) => {
+            condition.class = Some(class);
+            component
+        },
+        None => component
+    };
+    for feature in media.into_iter().flat_map(|media| media.split('@')) {
+        let feature = MediaFeature::parse(feature).ok_or_else(|| HTMLSceneSpawnError::InvalidValue(
+            attribute.to_string(), format!("Unrecognized media feature [{feature}]")
+        ))?;
+        condition.media.push(feature);
+    }
+    Ok((condition != StyleCondition::default()).then_some((component, condition)))
+}
+
+struct ConditionalStyle {
+    attribute: String,
+    condition: StyleCondition,
+    component: TypeId,
+    // Only the fields set in the attribute
+    patch: Box<dyn Reflect>
+}
+
+// Component values patched in while their conditions apply
+#[derive(Component, Default)]
+pub(crate) struct ConditionalStyles {
+    styles: Vec<ConditionalStyle>,
+    // Values of the patched components from before any condition applied
+    bases: HashMap<TypeId, Box<dyn Reflect>>,
+    // Whether each style applied last time
+    active: Vec<bool>
+}
+impl ConditionalStyles {
+    // Puts back the values the patches replaced, so they're reapplied from the current values
+    fn restore(&mut self, entity: &mut EntityWorldMut, type_registry: &TypeRegistry) {
+        for (component, base) in self.bases.drain() {
+            if let Some(reflect_component) = type_registry.get_type_data::<ReflectComponent>(component) {
+                reflect_component.apply(entity, &*base);
+            }
+        }
+        self.active.clear();
+    }
+
+    fn apply(&mut self, entity: &mut EntityWorldMut, type_registry: &TypeRegistry, active: Vec<bool>) {
+        let mut order: Vec<usize> = (0..self.styles.len()).collect();
+        // Attributes aren't kept in order, so ties go by name
+        order.sort_by(|a, b| {
+            let (a, b) = (&self.styles[*a], &self.styles[*b]);
+            (a.condition.precedence(), &a.attribute).cmp(&(b.condition.precedence(), &b.attribute))
+        });
+
+        let mut components: Vec<TypeId> = Vec::new();
+        for style in &self.styles {
+            if !components.contains(&style.component) {
+                components.push(style.component);
+            }
+        }
+
+        for component in components {
+            let Some(reflect_component) = type_registry.get_type_data::<ReflectComponent>(component) else { continue; };
+            let patches: Vec<&dyn Reflect> = order.iter()
+                .filter(|i| active[**i] && self.styles[**i].component == component)
+                .map(|i| &*self.styles[*i].patch)
+                .collect();
+
+            if patches.is_empty() {
+                if let Some(base) = self.bases.remove(&component) {
+                    reflect_component.apply(entity, &*base);
+                }
+                continue;
+            }
+            if !self.bases.contains_key(&component) {
+                let entity_ref = entity.world().entity(entity.id());
+                let Some(base) = reflect_component.reflect(entity_ref).map(|base| settled_value(entity_ref, component, base)) else { continue; };
+                self.bases.insert(component, base);
+            }
+            let mut value = self.bases[&component].clone_value();
+            for patch in patches {
+                value.apply(patch);
+            }
+            reflect_component.apply(entity, &*value);
+        }
+        self.active = active;
+    }
+}
+
+pub(crate) fn insert_conditional_style(
+    entity: &mut EntityWorldMut, type_registry: &TypeRegistry, attribute: &str, condition: StyleCondition, component: &TypeRegistration, value: Option<&str>
+) -> Result<(), HTMLSceneSpawnError> {
+    let patch = entity.world_scope(|world| match value {
+        Some(value) => deserialize_instance(world, type_registry, component, value),
+        None => construct_instance(world, type_registry, component, None)
+    })?;
+
+    let mut styles = entity.take::<ConditionalStyles>().unwrap_or_default();
+    styles.restore(entity, type_registry);
+    styles.styles.retain(|style| style.attribute != attribute);
+    styles.styles.push(ConditionalStyle { attribute: attribute.to_string(), condition, component: component.type_id(), patch });
+    entity.insert(styles);
+    Ok(())
+}
+
+pub(crate) fn remove_conditional_style(entity: &mut EntityWorldMut, type_registry: &TypeRegistry, attribute: &str) {
+    let Some(mut styles) = entity.take::<ConditionalStyles>() else { return; };
+    styles.restore(entity, type_registry);
+    styles.styles.retain(|style| style.attribute != attribute);
+    if !styles.styles.is_empty() {
+        entity.insert(styles);
+    }
+}
+
+// Restores the unpatched values before an element is morphed, so patched attributes don't get overwritten later
+pub(crate) fn restore_conditional_styles(entity: &mut EntityWorldMut, type_registry: &TypeRegistry) {
+    let Some(mut styles) = entity.take::<ConditionalStyles>() else { return; };
+    styles.restore(entity, type_registry);
+    entity.insert(styles);
+}
+
+// Re-patches elements whose conditions changed, e.g. when they're hovered or the window is resized
+pub(crate) fn apply_conditional_styles(world: &mut World) {
+    let focused = world.get_resource::<HtmlFocus>().and_then(|focus| focus.0);
+    let mut windows = world.query_filtered::<&Window, With<PrimaryWindow>>();
+    let mut styled = world.query::<(Entity, &ConditionalStyles, Option<&Interaction>, Has<Disabled>)>();
+    let window = windows.get_single(world).ok();
+    let changed: Vec<(Entity, Vec<bool>)> = styled.iter(world)
+        .filter_map(|(entity, styles, interaction, disabled)| {
+            let state = ElementState {
+                interaction: interaction.copied().unwrap_or(Interaction::None),
+                focused: focused == Some(entity),
+                disabled
+            };
+            let active: Vec<bool> = styles.styles.iter()
+                .map(|style| style.condition.applies(&state, window))
+                .collect();
+            (active != styles.active).then_some((entity, active))
+        })
+        .collect();
+    if changed.is_empty() {
+        return;
+    }
+
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+    for (entity, active) in changed {
+        let mut entity = world.entity_mut(entity);
+        let mut styles = entity.take::<ConditionalStyles>().unwrap();
+        styles.apply(&mut entity, &type_registry, active);
+        entity.insert(styles);
+    }
+}
+
+pub struct ConditionalStylePlugin;
+impl Plugin for ConditionalStylePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PostUpdate, apply_conditional_styles.before(UiSystem::Layout));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn condition(attribute: &str) -> Option<(&str, StyleCondition)> {
+        split_style_condition(attribute).unwrap()
+    }
+
+    #[test]
+    fn media_features_parse() {
+        assert_eq!(MediaFeature::parse("max-width:800"), Some(MediaFeature::MaxWidth(800.)));
+        assert_eq!(MediaFeature::parse("min-scale:1.5"), Some(MediaFeature::MinScale(1.5)));
+        assert_eq!(MediaFeature::parse("orientation:portrait"), Some(MediaFeature::Portrait));
+        assert_eq!(MediaFeature::parse("orientation:sideways"), None);
+        assert_eq!(MediaFeature::parse("max-width:wide"), None);
+        assert_eq!(MediaFeature::parse("max-width"), None);
+    }
+
+    #[test]
+    fn pseudo_classes_are_split_off() {
+        assert_eq!(condition("BackgroundColor:focus"), Some(("BackgroundColor", StyleCondition { class: Some(PseudoClass::Focus), media: vec![] })));
+        assert_eq!(condition("BackgroundColor"), None);
+    }
+
+    #[test]
+    fn pseudo_classes_combine_with_media_features() {
+        assert_eq!(
+            condition("BackgroundColor:hover@max-width:800"),
+            Some(("BackgroundColor", StyleCondition { class: Some(PseudoClass::Hover), media: vec![MediaFeature::MaxWidth(800.)] }))
+        );
+    }
+
+    #[test]
+    fn media_features_combine() {
+        assert_eq!(
+            condition("Style@min-width:600@max-width:1200@orientation:landscape"),
+            Some(("Style", StyleCondition {
+                class: None,
+                media: vec![MediaFeature::MinWidth(600.), MediaFeature::MaxWidth(1200.), MediaFeature::Landscape]
+            }))
+        );
+    }
+
+    #[test]
+    fn generic_attributes_are_left_alone() {
+        assert_eq!(condition("Handle:Image"), None);
+        assert_eq!(
+            condition("Handle:Image@max-width:800"),
+            Some(("Handle:Image", StyleCondition { class: None, media: vec![MediaFeature::MaxWidth(800.)] }))
+        );
+    }
+
+    #[test]
+    fn unrecognized_media_features_are_errors() {
+        let error = split_style_condition("Style@max-depth:3").unwrap_err();
+        assert_eq!(error.to_string(), "Attribute name [Style@max-depth:3]: Unrecognized media feature [max-depth:3]");
+        assert!(split_style_condition("Style:hover@wide").is_err());
+    }
+}
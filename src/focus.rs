@@ -0,0 +1,195 @@
+use bevy::prelude::*;
+use serde::{Serialize, Deserialize};
+
+// Makes an element focusable, written as the `tabindex` attribute.
+// Positive indices come first in Tab order, followed by 0 in tree order.
+// Negative indices can only be focused by clicking or from code.
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize, Default)]
+pub struct TabIndex(pub i32);
+
+// Focuses the element when it's spawned, written as the `autofocus` attribute
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize, Default)]
+pub struct AutoFocus;
+
+// Keeps an element from being focused or firing its triggers, written as the `disabled` attribute
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize, Default)]
+pub struct Disabled;
+
+// The focused element, which Enter and the south gamepad button activate like a click
+#[derive(Resource, Default, Debug, Clone)]
+pub struct HtmlFocus(pub Option<Entity>);
+
+// The focused element if it was activated this frame
+pub(crate) fn activated(
+    focus: &HtmlFocus, keys: &Input<KeyCode>, gamepads: &Gamepads, gamepad_buttons: &Input<GamepadButton>
+) -> Option<Entity> {
+    let pressed = keys.any_just_pressed([KeyCode::Return, KeyCode::NumpadEnter])
+        || gamepads.iter().any(|gamepad| gamepad_buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::South)));
+    focus.0.filter(|_| pressed)
+}
+
+fn autofocus(
+    added: Query<Entity, Added<AutoFocus>>,
+    mut focus: ResMut<HtmlFocus>
+) {
+    // The last element spawned wins
+    if let Some(entity) = added.iter().last() {
+        focus.0 = Some(entity);
+    }
+}
+
+fn focus_on_press(
+    interactions: Query<(Entity, &Interaction), (Changed<Interaction>, With<TabIndex>, Without<Disabled>)>,
+    mut focus: ResMut<HtmlFocus>
+) {
+    for (entity, interaction) in &interactions {
+        if *interaction == Interaction::Pressed {
+            focus.0 = Some(entity);
+        }
+    }
+}
+
+// Whether the stick was tilted past the navigation threshold last frame
+#[derive(Default)]
+struct StickTilted(bool);
+
+fn navigation_direction(
+    gamepads: &Gamepads,
+    gamepad_buttons: &Input<GamepadButton>,
+    axes: &Axis<GamepadAxis>,
+    tilted: &mut StickTilted
+) -> Option<Vec2> {
+    // In UI coordinates, with y pointing down
+    let dpad = [
+        (GamepadButtonType::DPadUp, Vec2::NEG_Y),
+        (GamepadButtonType::DPadDown, Vec2::Y),
+        (GamepadButtonType::DPadLeft, Vec2::NEG_X),
+        (GamepadButtonType::DPadRight, Vec2::X)
+    ];
+    let mut direction = None;
+    for gamepad in gamepads.iter() {
+        for (button, button_direction) in dpad {
+            if gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button)) {
+                direction = Some(button_direction);
+            }
+        }
+    }
+
+    let stick = gamepads.iter()
+        .map(|gamepad| Vec2::new(
+            axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX)).unwrap_or(0.),
+            -axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY)).unwrap_or(0.)
+        ))
+        .max_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+        .unwrap_or(Vec2::ZERO);
+    // Move once per tilt, releasing below a lower threshold so the stick doesn't jitter
+    if !tilted.0 && stick.length() > 0.6 {
+        tilted.0 = true;
+        direction = Some(match stick.x.abs() > stick.y.abs() {
+            true => Vec2::new(stick.x.signum(), 0.),
+            false => Vec2::new(0., stick.y.signum())
+        });
+    } else if tilted.0 && stick.length() < 0.3 {
+        tilted.0 = false;
+    }
+
+    direction
+}
+
+fn navigate_focus(
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut tilted: Local<StickTilted>,
+    focusables: Query<(&TabIndex, &Node, &GlobalTransform, Option<&InheritedVisibility>), Without<Disabled>>,
+    roots: Query<Entity, (With<Node>, Without<Parent>)>,
+    children: Query<&Children>,
+    mut focus: ResMut<HtmlFocus>
+) {
+    // Drop focus from elements that went away or were disabled
+    if focus.0.is_some_and(|entity| !focusables.contains(entity)) {
+        focus.0 = None;
+    }
+
+    let tab = keys.just_pressed(KeyCode::Tab);
+    let direction = navigation_direction(&gamepads, &gamepad_buttons, &axes, &mut tilted);
+    if !tab && direction.is_none() {
+        return;
+    }
+
+    // Focusable elements that are shown, in tree order
+    let mut roots: Vec<Entity> = roots.iter().collect();
+    roots.sort();
+    let mut order: Vec<(Entity, i32)> = Vec::new();
+    let mut stack: Vec<Entity> = roots.into_iter().rev().collect();
+    while let Some(entity) = stack.pop() {
+        if let Ok((index, node, _, visibility)) = focusables.get(entity) {
+            if index.0 >= 0 && node.size() != Vec2::ZERO && visibility.map_or(true, |v| v.get()) {
+                order.push((entity, index.0));
+            }
+        }
+        if let Ok(children) = children.get(entity) {
+            stack.extend(children.iter().rev());
+        }
+    }
+    order.sort_by_key(|(_, index)| match *index {
+        0 => i32::MAX,
+        index => index
+    });
+    let order: Vec<Entity> = order.into_iter().map(|(entity, _)| entity).collect();
+    if order.is_empty() {
+        return;
+    }
+
+    let current = focus.0.and_then(|focused| order.iter().position(|entity| *entity == focused));
+    if tab {
+        let back = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        focus.0 = Some(match (current, back) {
+            (Some(i), false) => order[(i + 1) % order.len()],
+            (Some(i), true) => order[(i + order.len() - 1) % order.len()],
+            (None, false) => order[0],
+            (None, true) => order[order.len() - 1]
+        });
+        return;
+    }
+
+    let Some(direction) = direction else { return; };
+    let Some(current) = current else {
+        focus.0 = Some(order[0]);
+        return;
+    };
+    // Closest element in the direction, preferring ones in line with the focused element
+    let center = |entity: Entity| {
+        let (_, node, transform, _) = focusables.get(entity).unwrap();
+        node.logical_rect(transform).center()
+    };
+    let from = center(order[current]);
+    let next = order.iter()
+        .filter_map(|entity| {
+            let offset = center(*entity) - from;
+            let along = offset.dot(direction);
+            let across = offset.perp_dot(direction).abs();
+            (along > 0.).then_some((*entity, along + across * 2.))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+    if let Some((next, _)) = next {
+        focus.0 = Some(next);
+    }
+}
+
+pub struct FocusPlugin;
+impl Plugin for FocusPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .register_type::<TabIndex>()
+            .register_type::<AutoFocus>()
+            .register_type::<Disabled>()
+            .init_resource::<HtmlFocus>()
+
+            .add_systems(Update, (autofocus, focus_on_press, navigate_focus).chain());
+    }
+}
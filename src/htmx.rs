@@ -1,12 +1,37 @@
+use std::any::TypeId;
+use std::fmt::{self, Display, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
 use bevy::prelude::*;
-use bevy::{ecs::component::Component, reflect::ReflectDeserialize};
+use bevy::ui::CalculatedClip;
+use bevy::window::PrimaryWindow;
+use bevy::utils::HashMap;
+use bevy::{ecs::{component::Component, entity::Entities, system::SystemParam}, reflect::{ReflectDeserialize, ReflectFromReflect}};
+use bevy::tasks::{AsyncComputeTaskPool, Task, block_on};
 use bevy::ecs::reflect::ReflectComponent;
 use bevy::reflect::std_traits::ReflectDefault;
-use bevy::reflect::Reflect;
-use serde::{Serialize, Deserialize};
+use bevy::reflect::{Reflect, FromType, erased_serde};
+use maud::html;
+use serde::{Serialize, Deserialize, Deserializer, de::{DeserializeSeed, Visitor, SeqAccess, Error}};
+
+use crate::{HTMLScene, XSlot, ReflectConstruct, spawn_scene, morph_scene, spawn_scene_system, HTMLSceneInstance};
+use crate::named_system_registry::{NamedSystemRegistry, NamedSystemError};
+use crate::typed_partial_reflect_deserializer::TypedPartialReflectDeserializer;
+use crate::router::{HtmlRouter, XRoute, XOutlet, router_back_button, render_routes};
+use crate::focus::{HtmlFocus, Disabled, activated};
+use crate::transition::parse_seconds;
 
-use crate::{HTMLScene, spawn_scene_system, HTMLSceneInstance};
-use crate::named_system_registry::NamedSystemRegistry;
+#[cfg(feature = "http")]
+mod http;
+mod phases;
+pub use phases::*;
+mod form;
+pub use form::*;
+mod emit;
+pub use emit::*;
+mod pipeline;
+pub use pipeline::*;
 
 #[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
 #[reflect(Component, Deserialize, Default)]
@@ -15,7 +40,13 @@ pub enum XSwap {
     Outer,
     Inner,
     Front,
-    Back
+    Back,
+    BeforeBegin,
+    AfterEnd,
+    Attributes, // Only applies the root's attributes, leaving children untouched
+    Morph, // Like Outer, but patches the existing entities instead of respawning them
+    Delete,
+    None
 }
 #[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
 #[reflect(Component, Deserialize, Default)]
@@ -36,107 +67,1041 @@ pub enum XOn {
     Create,
     Update,
     Fixed(f32),
-    Click,
+    Click, // Also fires when focused and activated with Enter or the south gamepad button
+    HoverEnter,
+    HoverLeave,
+    Release, // Click completed while still over the element
+    Key(KeyCode), // Only while no modifier keys are held
+    KeyWith(KeyCode, KeyModifiers),
+    Gamepad(GamepadButtonType), // On any connected gamepad
+    Visible, // Once the node is inside its clipping ancestors and the window
     Event(String)
 }
+// Modifier keys that must be held for XOn::KeyWith, either side counts
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Reflect)]
+#[reflect(Deserialize, Default)]
+#[serde(default)]
+pub struct KeyModifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub super_key: bool
+}
+impl KeyModifiers {
+    fn pressed(keys: &Input<KeyCode>) -> Self {
+        Self {
+            ctrl: keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]),
+            shift: keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]),
+            alt: keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]),
+            super_key: keys.any_pressed([KeyCode::SuperLeft, KeyCode::SuperRight])
+        }
+    }
+}
+// A time written like htmx and x-transition, e.g. "300ms" or "1s", in seconds
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Reflect)]
+#[reflect_value(Deserialize, Default)]
+#[serde(try_from = "String", into = "String")]
+pub struct Seconds(pub f32);
+impl TryFrom<String> for Seconds {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        parse_seconds(&value).map(Seconds).ok_or_else(|| format!("Invalid time [{value}], expected e.g. \"300ms\" or \"1s\""))
+    }
+}
+impl From<Seconds> for String {
+    fn from(value: Seconds) -> Self {
+        format!("{}s", value.0)
+    }
+}
+// Modifiers applied on top of the trigger's XOn,
+// e.g. XOnModifiers="once: true" or XOnModifiers='delay: "300ms", changed: true'
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize, Default)]
+#[serde(default)]
+pub struct XOnModifiers {
+    pub once: bool, // Only fire the first time
+    pub delay: Seconds, // Wait until the trigger has been quiet for this long (debounce)
+    pub throttle: Seconds, // Ignore triggers for this long after firing
+    pub changed: bool, // Only fire if the element's input value changed since the last trigger, see ReflectFormValue
+    pub from: Option<XTarget> // Evaluate the trigger on another element
+}
+// Per trigger state kept for XOnModifiers
+#[derive(Component, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Default)]
+pub struct XOnState {
+    pub fired: bool,
+    pub delay: Option<Timer>,
+    pub throttle: Option<Timer>,
+    pub value: Option<String> // Input value when last triggered, None until then
+}
+impl XOnState {
+    fn update(&mut self, modifiers: &XOnModifiers, mut triggered: bool, value: String, delta: Duration) -> bool {
+        if triggered && modifiers.changed {
+            triggered = self.value.as_ref() != Some(&value);
+            self.value = Some(value);
+        }
+
+        if let Some(throttle) = self.throttle.as_mut() {
+            if throttle.tick(delta).finished() {
+                self.throttle = None;
+            }
+        }
+        if modifiers.delay.0 > 0. {
+            if triggered {
+                // Restart the wait on every trigger
+                self.delay = Some(Timer::from_seconds(modifiers.delay.0, TimerMode::Once));
+                triggered = false;
+            } else if let Some(delay) = self.delay.as_mut() {
+                if delay.tick(delta).finished() {
+                    self.delay = None;
+                    triggered = true;
+                }
+            }
+        }
+
+        if !triggered || (modifiers.once && self.fired) || self.throttle.is_some() {
+            return false;
+        }
+        if modifiers.throttle.0 > 0. {
+            self.throttle = Some(Timer::from_seconds(modifiers.throttle.0, TimerMode::Once));
+        }
+        self.fired = true;
+        true
+    }
+}
+// Input value of a trigger with the `changed` modifier, read before triggers are found
+#[derive(Component)]
+struct TriggerValue(String);
+
+fn read_trigger_values(world: &mut World) {
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+    let mut triggers = world.query::<(Entity, &XOnModifiers)>();
+    let values: Vec<(Entity, String)> = triggers.iter(world)
+        .filter(|(_, modifiers)| modifiers.changed)
+        .map(|(entity, _)| (entity, element_value(world, &type_registry, entity).unwrap_or_default()))
+        .collect();
+    for (entity, value) in values {
+        world.entity_mut(entity).insert(TriggerValue(value));
+    }
+}
+
+// Element shown while an HTMLTask started by this trigger is pending
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize)]
+pub struct XIndicator(pub XTarget);
+// Name of a named system, plus optional arguments deserialized into the system's `In` type,
+// e.g. XFunction="\"increment\"" or XFunction="(\"add\", (amount: 5))"
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize)]
+pub struct XFunction(pub String, #[serde(skip)] pub Option<XArgs>);
+
+// Fetch a scene from an HTTP endpoint, sending the enclosing XForm (requires the `http` feature)
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize)]
+pub struct XGet(pub String);
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize)]
+pub struct XPost(pub String);
+// Name of an input in the enclosing XForm, whose value is taken from a ReflectFormValue component
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize)]
+pub struct XName(pub String);
+// Keeps the element's entity when swapped out, moving it in place of the new element with the same id
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize, Default)]
+pub struct XPreserve;
+// Preserved entities detached from a swapped out subtree, waiting for the new scene to spawn
+#[derive(Component, Default)]
+pub(crate) struct HTMLPreserved(HashMap<String, Entity>);
+
+#[derive(Debug, Reflect)]
+#[reflect_value]
+pub struct XArgs(pub Box<dyn Reflect>);
+impl Clone for XArgs {
+    fn clone(&self) -> Self {
+        XArgs(self.0.clone_value())
+    }
+}
+
+// Several swaps returned from a single named system, targets are relative to the triggering entity.
+// Top level elements of a scene with an `x-swap-oob` attribute are also swapped into the element with a matching id.
+#[derive(Reflect, Default, Debug, Clone)]
+pub struct HTMLResponse(pub Vec<(XTarget, XSwap, HTMLScene)>);
+impl HTMLResponse {
+    pub fn swap(mut self, target: XTarget, swap: XSwap, scene: impl Into<HTMLScene>) -> Self {
+        self.0.push((target, swap, scene.into()));
+        self
+    }
+
+    // Malformed out of band elements fail the whole response, since it often comes from a server
+    fn with_out_of_band(self) -> Result<Self, String> {
+        let mut swaps = Vec::new();
+        for (target, swap, scene) in self.0 {
+            let (nodes, out_of_band): (Vec<_>, Vec<_>) = scene.dom().children.iter().cloned()
+                .partition(|node| !node.element().map(|el| el.attributes.contains_key("x-swap-oob")).unwrap_or(false));
+            if out_of_band.is_empty() {
+                swaps.push((target, swap, scene));
+                continue;
+            }
+
+            if nodes.iter().any(|node| node.element().is_some()) {
+                swaps.push((target, swap, HTMLScene::from(nodes)));
+            }
+            for node in out_of_band {
+                let html_parser::Node::Element(mut el) = node else { unreachable!() };
+                let swap = match el.attributes.remove("x-swap-oob").flatten() {
+                    Some(value) => ron::from_str::<XSwap>(&html_escape::decode_html_entities(&value))
+                        .map_err(|error| format!("Invalid x-swap-oob value [{value}]: {error}"))?,
+                    None => XSwap::default()
+                };
+                let target = XTarget::Name(el.id.clone().ok_or_else(|| format!("Out of band swap element <{}> is missing an id", el.name))?);
+                if matches!(swap, XSwap::Outer | XSwap::Attributes | XSwap::Morph | XSwap::Delete | XSwap::None) {
+                    swaps.push((target, swap, HTMLScene::from(vec![html_parser::Node::Element(el)])));
+                    continue;
+                }
+
+                // Other swaps insert the element's children, like htmx
+                let mut children: Vec<_> = el.children.into_iter().filter(|child| child.element().is_some()).collect();
+                if matches!(swap, XSwap::Front | XSwap::AfterEnd) {
+                    children.reverse();
+                }
+                for (i, child) in children.into_iter().enumerate() {
+                    // Only the first child replaces the target's contents
+                    let swap = if i > 0 && matches!(swap, XSwap::Inner) { XSwap::Back } else { swap.clone() };
+                    swaps.push((target.clone(), swap, HTMLScene::from(vec![child])));
+                }
+            }
+        }
+        Ok(HTMLResponse(swaps))
+    }
+}
+
+// Returned from a named system to build its scene asynchronously, e.g. on the AsyncComputeTaskPool.
+// The trigger is disabled and its XIndicator shown until the task completes, and the task is cancelled
+// if the trigger or target are despawned in the meantime.
+#[derive(Reflect, Clone)]
+#[reflect_value]
+pub struct HTMLTask(Arc<Mutex<Option<Task<Result<HTMLScene, String>>>>>);
+impl From<Task<Result<HTMLScene, String>>> for HTMLTask {
+    fn from(task: Task<Result<HTMLScene, String>>) -> Self {
+        HTMLTask(Arc::new(Mutex::new(Some(task))))
+    }
+}
+impl From<Task<HTMLScene>> for HTMLTask {
+    fn from(task: Task<HTMLScene>) -> Self {
+        AsyncComputeTaskPool::get().spawn(async move { Ok(task.await) }).into()
+    }
+}
+
+// What a named system's output means for its trigger
+pub enum HTMLOutput {
+    None, // Side effects only, nothing is swapped
+    Scene(HTMLScene),
+    Response(HTMLResponse),
+    Task(Task<Result<HTMLScene, String>>),
+    Error(String)
+}
+// Types XFunction named systems can return, registered as ReflectIntoHTMLOutput type data
+pub trait IntoHTMLOutput {
+    fn into_output(self) -> HTMLOutput;
+}
+impl IntoHTMLOutput for () {
+    fn into_output(self) -> HTMLOutput {
+        HTMLOutput::None
+    }
+}
+impl IntoHTMLOutput for HTMLScene {
+    fn into_output(self) -> HTMLOutput {
+        HTMLOutput::Scene(self)
+    }
+}
+impl IntoHTMLOutput for HTMLResponse {
+    fn into_output(self) -> HTMLOutput {
+        HTMLOutput::Response(self)
+    }
+}
+impl IntoHTMLOutput for HTMLTask {
+    fn into_output(self) -> HTMLOutput {
+        match self.0.lock().unwrap().take() {
+            Some(task) => HTMLOutput::Task(task),
+            None => HTMLOutput::Error("Returned an HTMLTask that was already taken".to_string())
+        }
+    }
+}
+impl<T: IntoHTMLOutput> IntoHTMLOutput for Option<T> {
+    fn into_output(self) -> HTMLOutput {
+        self.map(T::into_output).unwrap_or(HTMLOutput::None)
+    }
+}
+impl<T: IntoHTMLOutput, E: Display> IntoHTMLOutput for Result<T, E> {
+    fn into_output(self) -> HTMLOutput {
+        match self {
+            Ok(output) => output.into_output(),
+            Err(error) => HTMLOutput::Error(error.to_string())
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ReflectIntoHTMLOutput {
+    pub func: fn(this: Box<dyn Reflect>) -> HTMLOutput
+}
+impl<T: IntoHTMLOutput + Reflect> FromType<T> for ReflectIntoHTMLOutput {
+    fn from_type() -> Self {
+        Self {
+            func: |this: Box<dyn Reflect>| -> HTMLOutput {
+                let this: T = *this.downcast().unwrap();
+                this.into_output()
+            }
+        }
+    }
+}
+impl ReflectIntoHTMLOutput {
+    pub fn into_output(&self, this: Box<dyn Reflect>) -> HTMLOutput {
+        (self.func)(this)
+    }
+}
+
+// Sent when a named system or HTMLTask fails
+#[derive(Event, Debug, Clone)]
+pub struct HtmlError {
+    pub trigger: Entity,
+    pub error: String
+}
+// Element the error message is swapped into when the trigger's named system fails
 #[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
 #[reflect(Component, Deserialize)]
-pub struct XFunction(pub String);
+pub struct XErrorTarget(pub XTarget);
+
+fn error_response(error_target: Option<&XErrorTarget>, error: &str) -> Option<HTMLResponse> {
+    let error_target = error_target?;
+    Some(HTMLResponse::default().swap(error_target.0.clone(), XSwap::Inner, html! { Text { (error) } }))
+}
+
+struct PendingTask {
+    trigger: Entity,
+    target: Entity,
+    swap: XSwap,
+    indicator: Option<Entity>,
+    task: Task<Result<HTMLScene, String>>
+}
+#[derive(Resource, Default)]
+struct PendingTasks(Vec<PendingTask>);
+impl PendingTasks {
+    fn contains(&self, trigger: Entity) -> bool {
+        self.0.iter().any(|pending| pending.trigger == trigger)
+    }
+}
+
+// Context of the request that triggered a named system, only available while it runs
+#[derive(Resource, Debug, Clone, Reflect)]
+pub struct HtmlRequest {
+    pub trigger: Entity,
+    pub target: Entity,
+    pub on: XOn
+}
 
-type ToRun = (Entity, XFunction, XOn, XSwap, XTarget);
+struct XFunctionSeed<'a>(&'a mut World);
+impl<'a, 'de> DeserializeSeed<'de> for XFunctionSeed<'a> {
+    type Value = XFunction;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de> {
+        deserializer.deserialize_any(self)
+    }
+}
+impl<'a, 'de> Visitor<'de> for XFunctionSeed<'a> {
+    type Value = XFunction;
+
+    fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str("named system name, or tuple of name and arguments")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: Error, {
+        Ok(XFunction(value.to_string(), None))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>, {
+        let world = self.0;
+        // Either the tuple struct wrapper around the name, or the (name, args) tuple
+        let XFunction(name, args) = seq.next_element_seed(XFunctionSeed(&mut *world))?
+            .ok_or_else(|| Error::custom("Missing named system name"))?;
+        if args.is_some() {
+            return Ok(XFunction(name, args));
+        }
+        let args = seq.next_element_seed(XArgsSeed { world, name: &name })?;
+        Ok(XFunction(name, args))
+    }
+}
+
+struct XArgsSeed<'a> {
+    world: &'a mut World,
+    name: &'a str
+}
+impl<'a, 'de> DeserializeSeed<'de> for XArgsSeed<'a> {
+    type Value = XArgs;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de> {
+        // Arguments are passed to the first named system of a pipeline
+        let name = pipeline_stages(self.name)[0];
+        let (in_type, _) = self.world.resource::<NamedSystemRegistry>().get_type_ids(name)
+            .ok_or_else(|| Error::custom(format_args!("Named system [{}] isn't registered", name)))?;
+
+        let type_registry = self.world.resource::<AppTypeRegistry>().clone();
+        let type_registry = type_registry.read();
+        let registration = type_registry.get(in_type)
+            .ok_or_else(|| Error::custom(format_args!("Named system [{}]: Input type not in type registry", self.name)))?;
+
+        TypedPartialReflectDeserializer::new(self.world, registration, &type_registry, true)
+            .deserialize(deserializer)
+            .map(XArgs)
+    }
+}
+
+// Failures are logged, since the fallback deserialization only reports a generic error
+fn construct_x_function(world: &mut World, deserializer: &mut dyn erased_serde::Deserializer) -> Option<Box<dyn Reflect>> {
+    match XFunctionSeed(world).deserialize(deserializer) {
+        Ok(func) => Some(Box::new(func)),
+        Err(error) => {
+            error!("XFunction: {error}");
+            None
+        }
+    }
+}
+
+#[derive(SystemParam)]
+pub(crate) struct XTargets<'w, 's> {
+    names: Query<'w, 's, (Entity, &'static Name)>,
+    children: Query<'w, 's, &'static Children>,
+    parents: Query<'w, 's, &'static Parent>,
+    slots: Query<'w, 's, (), With<XSlot>>
+}
+impl<'w, 's> XTargets<'w, 's> {
+    // The entity swaps into the element's children go in, see XSlot
+    pub(crate) fn slot(&self, entity: Entity) -> Entity {
+        self.children.get(entity).into_iter().flatten()
+            .find(|child| self.slots.contains(**child))
+            .copied()
+            .unwrap_or(entity)
+    }
+
+    // Fails if the target isn't in the tree, e.g. an out of band swap's id sent by a server
+    pub(crate) fn resolve(&self, entity: Entity, target: &XTarget) -> Result<Entity, String> {
+        match target {
+            XTarget::This => Ok(entity),
+            XTarget::Name(name) => self.names.iter().find(|(_, n)| n.as_str() == name).map(|(entity, _)| entity)
+                                       .ok_or_else(|| format!("No element with id [{name}]")),
+            XTarget::ChildName(name) => self.children.iter_descendants(entity)
+                                            .find(|d| self.names.get(*d).map(|(_, n)| n.as_str() == name).unwrap_or(false))
+                                            .ok_or_else(|| format!("No descendant with id [{name}]")),
+            XTarget::Root => Ok(self.parents.iter_ancestors(entity).last().unwrap_or(entity)),
+            XTarget::NextSibling | XTarget::PreviousSibling => {
+                let siblings = self.parents.get(entity).ok()
+                    .and_then(|parent| self.children.get(parent.get()).ok())
+                    .ok_or_else(|| format!("Target {target:?}: Root elements have no siblings"))?;
+                let index = siblings.iter().position(|sibling| *sibling == entity).unwrap();
+                let index = match target {
+                    XTarget::NextSibling => index.checked_add(1),
+                    _ => index.checked_sub(1)
+                };
+                index.and_then(|index| siblings.get(index)).copied()
+                    .ok_or_else(|| format!("Target {target:?}: No such sibling"))
+            },
+            XTarget::Entity(target) => Ok(*target)
+        }
+    }
+}
+
+// Marks XOn::Visible triggers that already fired
+#[derive(Component, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Default)]
+pub struct XRevealed;
+
+#[derive(SystemParam)]
+pub(crate) struct XVisibility<'w, 's> {
+    nodes: Query<'w, 's, (&'static Node, &'static GlobalTransform, Option<&'static CalculatedClip>, Option<&'static InheritedVisibility>)>,
+    windows: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    revealed: Query<'w, 's, (), With<XRevealed>>
+}
+impl<'w, 's> XVisibility<'w, 's> {
+    // Whether the node's rect intersects the clip of its ancestors and the primary window
+    pub(crate) fn visible(&self, entity: Entity) -> bool {
+        let Ok((node, transform, clip, visibility)) = self.nodes.get(entity) else { return false; };
+        if visibility.map(|v| !v.get()).unwrap_or(false) {
+            return false;
+        }
+        let mut rect = node.logical_rect(transform);
+        if let Some(clip) = clip {
+            rect = rect.intersect(clip.clip);
+        }
+        if let Ok(window) = self.windows.get_single() {
+            rect = rect.intersect(Rect::new(0., 0., window.width(), window.height()));
+        }
+        !rect.is_empty()
+    }
+}
+
+struct ToRun {
+    swap: XSwap,
+    indicator: Option<Entity>,
+    request: HtmlRequest
+}
 
 fn find_to_run(
+    time: Res<Time>,
     created_entities: Query<(), Added<Transform>>,
-    interactions: Query<&Interaction, Changed<Interaction>>,
-    x_entities: Query<(Entity, &XFunction, Option<&XOn>, Option<&XSwap>, Option<&XTarget>)>
+    interactions: Query<(Entity, &Interaction), Changed<Interaction>>,
+    mut removed_interactions: RemovedComponents<Interaction>,
+    mut last_interactions: Local<HashMap<Entity, Interaction>>,
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    focus: Res<HtmlFocus>,
+    values: Query<&TriggerValue>,
+    x_entities: Query<
+        (Entity, Option<&XOn>, Option<&XOnModifiers>, Option<&XSwap>, Option<&XTarget>, Option<&XIndicator>),
+        (Or<(With<XFunction>, With<XEmit>, With<XGet>, With<XPost>, With<XRoute>)>, Without<Disabled>)
+    >,
+    mut states: Query<&mut XOnState>,
+    pending_tasks: Res<PendingTasks>,
+    mut swap_errors: ResMut<SwapErrors>,
+    targets: XTargets,
+    visibility: XVisibility,
+    mut commands: Commands
 ) -> Vec<ToRun> {
     let mut to_run = Vec::new();
 
-    for (entity, func, on, swap, target) in &x_entities {
-        let func = func.clone();
+    // Interaction changes this frame, paired with the previous interaction
+    for entity in removed_interactions.read() {
+        last_interactions.remove(&entity);
+    }
+    let transitions: HashMap<Entity, (Interaction, Interaction)> = interactions.iter()
+        .map(|(entity, interaction)| {
+            let last = last_interactions.insert(entity, *interaction).unwrap_or(Interaction::None);
+            (entity, (last, *interaction))
+        })
+        .collect();
+    let key_modifiers = KeyModifiers::pressed(&keys);
+    let activated = activated(&focus, &keys, &gamepads, &gamepad_buttons);
+
+    for (entity, on, modifiers, swap, target, indicator) in &x_entities {
+        // Triggers are disabled while waiting on a task
+        if pending_tasks.contains(entity) {
+            continue;
+        }
+
         let on = on.cloned().unwrap_or_default();
         let swap = swap.cloned().unwrap_or_default();
-        let target = target.cloned().unwrap_or_default();
 
-        if match on {
-            XOn::Create => created_entities.contains(entity),
-            XOn::Click => interactions.get(entity)
-                            .map(|i| matches!(i, Interaction::Pressed))
-                            .unwrap_or(false),
+        // The element the trigger listens on, only resolved when there's an event it could match.
+        // A `from` element that isn't in the tree (yet) never triggers.
+        let from = modifiers.and_then(|modifiers| modifiers.from.as_ref());
+        let source = |events: bool| match (events, from) {
+            (false, _) => None,
+            (true, Some(from)) => targets.resolve(entity, from).ok(),
+            (true, None) => Some(entity)
+        };
+        let interacted = !transitions.is_empty();
+        let triggered = match on {
+            XOn::Create => source(!created_entities.is_empty())
+                            .is_some_and(|source| created_entities.contains(source)),
+            XOn::Click => source(interacted || activated.is_some())
+                            .is_some_and(|source| matches!(transitions.get(&source), Some((_, Interaction::Pressed))) || activated == Some(source)),
+            XOn::HoverEnter => source(interacted)
+                            .is_some_and(|source| matches!(transitions.get(&source), Some((Interaction::None, Interaction::Hovered | Interaction::Pressed)))),
+            XOn::HoverLeave => source(interacted)
+                            .is_some_and(|source| matches!(transitions.get(&source), Some((Interaction::Hovered | Interaction::Pressed, Interaction::None)))),
+            XOn::Release => source(interacted)
+                            .is_some_and(|source| matches!(transitions.get(&source), Some((Interaction::Pressed, Interaction::Hovered)))),
+            XOn::Key(key) => keys.just_pressed(key) && key_modifiers == KeyModifiers::default(),
+            XOn::KeyWith(key, ref modifiers) => keys.just_pressed(key) && key_modifiers == *modifiers,
+            XOn::Gamepad(button) => gamepads.iter()
+                            .any(|gamepad| gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button))),
+            XOn::Visible => match source(true) {
+                Some(source) if !visibility.revealed.contains(source) && visibility.visible(source) => {
+                    commands.entity(source).insert(XRevealed);
+                    true
+                },
+                _ => false
+            },
             XOn::Update => true,
             _ => unimplemented!()
-        } {
-            to_run.push((entity, func, on, swap, target))
+        };
+        let triggered = match modifiers {
+            Some(modifiers) => {
+                let value = values.get(entity).map(|value| value.0.clone()).unwrap_or_default();
+                match states.get_mut(entity) {
+                    Ok(mut state) => state.update(modifiers, triggered, value, time.delta()),
+                    Err(_) => {
+                        let mut state = XOnState::default();
+                        let triggered = state.update(modifiers, triggered, value, time.delta());
+                        commands.entity(entity).insert(state);
+                        triggered
+                    }
+                }
+            },
+            None => triggered
+        };
+
+        if triggered {
+            let target = targets.resolve(entity, &target.cloned().unwrap_or_default());
+            let indicator = indicator.map(|indicator| targets.resolve(entity, &indicator.0)).transpose();
+            match (target, indicator) {
+                (Ok(target), Ok(indicator)) => to_run.push(ToRun { swap, indicator, request: HtmlRequest { trigger: entity, target, on } }),
+                (Err(error), _) | (_, Err(error)) => swap_errors.0.push((entity, error))
+            }
         }
     }
 
     to_run
 }
 
+// Detaches preserved descendants of `entity` before they're despawned, handing them over to `to`
+fn preserve_descendants(world: &mut World, entity: Entity, to: Entity) {
+    let mut preserved = HashMap::new();
+    let mut stack: Vec<Entity> = world.get::<Children>(entity).map(|c| c.to_vec()).unwrap_or_default();
+    while let Some(child) = stack.pop() {
+        match world.get::<Name>(child) {
+            Some(name) if world.get::<XPreserve>(child).is_some() => {
+                preserved.insert(name.to_string(), child);
+                world.entity_mut(child).remove_parent();
+            },
+            _ => stack.extend(world.get::<Children>(child).into_iter().flat_map(|c| c.iter().copied()))
+        }
+    }
+    if !preserved.is_empty() {
+        world.entity_mut(to).insert(HTMLPreserved(preserved));
+    }
+}
+
+// Swaps the preserved entities into a freshly spawned scene in place of their new counterparts
+pub(crate) fn restore_preserved(world: &mut World, root: Entity) {
+    let Some(HTMLPreserved(mut preserved)) = world.entity_mut(root).take::<HTMLPreserved>() else { return; };
+    let mut stack = vec![root];
+    while let Some(entity) = stack.pop() {
+        let old = world.get::<Name>(entity)
+            .filter(|_| world.get::<XPreserve>(entity).is_some())
+            .and_then(|name| preserved.remove(name.as_str()));
+        let Some(old) = old else {
+            stack.extend(world.get::<Children>(entity).into_iter().flat_map(|c| c.iter().copied()));
+            continue;
+        };
+        let Some(parent) = world.get::<Parent>(entity).map(|p| p.get()) else { continue; };
+        let index = world.get::<Children>(parent).unwrap().iter().position(|c| *c == entity).unwrap();
+        world.entity_mut(entity).despawn_recursive();
+        world.entity_mut(parent).insert_children(index, &[old]);
+    }
+    // Preserved elements missing from the new scene are dropped
+    for (_, old) in preserved {
+        world.entity_mut(old).despawn_recursive();
+    }
+}
+
+fn set_indicator(world: &mut World, indicator: Option<Entity>, visibility: Visibility) {
+    if let Some(mut indicator) = indicator.and_then(|indicator| world.get_mut::<Visibility>(indicator)) {
+        *indicator = visibility;
+    }
+}
+
+fn call_x_function(
+    world: &mut World, func: &XFunction, request: HtmlRequest
+) -> Result<Box<dyn Reflect>, String> {
+    let stages = pipeline_stages(&func.0);
+    let named_system_registry = world.resource::<NamedSystemRegistry>();
+    let (in_type, _) = named_system_registry.get_type_ids(stages[0])
+        .ok_or_else(|| NamedSystemError::NotFound { name: stages[0].to_string(), suggestions: named_system_registry.similar_names(stages[0]) }.to_string())?;
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+
+    // Explicit arguments take precedence over the enclosing form's values
+    let args = match &func.1 {
+        Some(args) => Some(args.0.clone_value()),
+        None if in_type != TypeId::of::<()>() => collect_form(world, &type_registry, request.trigger)
+            .map(|form| form_input(world, &type_registry, form, in_type)
+                .map_err(|e| format!("Named system [{}]: Failed to convert form to input type: {e}", stages[0])))
+            .transpose()?,
+        None => None
+    };
+    let input: Box<dyn Reflect> = match args {
+        Some(args) if args.as_any().type_id() == in_type => args,
+        Some(args) => type_registry.get_type_data::<ReflectFromReflect>(in_type)
+            .and_then(|from_reflect| from_reflect.from_reflect(&*args))
+            .ok_or_else(|| NamedSystemError::WrongInput {
+                name: stages[0].to_string(),
+                expected: world.resource::<NamedSystemRegistry>().get_type_paths(stages[0]).unwrap().0,
+                actual: args.reflect_type_path().to_string()
+            }.to_string())?,
+        None => Box::new(())
+    };
+    drop(type_registry);
+
+    // Named systems can trigger others, so restore the outer request afterwards
+    let outer_request = world.remove_resource::<HtmlRequest>();
+    world.insert_resource(request);
+    let res = call_pipeline(world, &stages, input);
+    world.remove_resource::<HtmlRequest>();
+    if let Some(outer_request) = outer_request {
+        world.insert_resource(outer_request);
+    }
+    res
+}
+
+// Calls a trigger's named system, returning the swaps its output asks for, or starting its task
+pub(crate) fn run_x_function(
+    world: &mut World, func: &XFunction, request: HtmlRequest, swap: XSwap, indicator: Option<Entity>
+) -> Option<HTMLResponse> {
+    let (trigger, target) = (request.trigger, request.target);
+    let output = call_x_function(world, func, request)
+        .and_then(|res| {
+            let into_output = world.resource::<AppTypeRegistry>().read()
+                .get_type_data::<ReflectIntoHTMLOutput>(res.as_any().type_id())
+                .cloned()
+                .ok_or_else(|| format!(
+                    "Named system [{}]: Output type {} is missing ReflectIntoHTMLOutput type data", func.0, res.reflect_type_path()
+                ))?;
+            Ok(match into_output.into_output(res) {
+                HTMLOutput::Error(error) => HTMLOutput::Error(format!("Named system [{}]: {error}", func.0)),
+                output => output
+            })
+        })
+        .unwrap_or_else(HTMLOutput::Error);
+    match output {
+        HTMLOutput::None => None,
+        HTMLOutput::Scene(scene) => Some(HTMLResponse::default().swap(XTarget::Entity(target), swap, scene)),
+        HTMLOutput::Response(response) => Some(response),
+        HTMLOutput::Task(task) => {
+            start_task(world, PendingTask { trigger, target, swap, indicator, task });
+            None
+        },
+        HTMLOutput::Error(error) => {
+            let response = error_response(world.get::<XErrorTarget>(trigger), &error);
+            world.send_event(HtmlError { trigger, error });
+            response
+        }
+    }
+}
+
+fn start_task(world: &mut World, task: PendingTask) {
+    set_indicator(world, task.indicator, Visibility::Inherited);
+    world.resource_mut::<PendingTasks>().0.push(task);
+}
+
 fn run_x_funcs(
     to_run: In<Vec<ToRun>>, world: &mut World
-) -> Vec<(ToRun, HTMLScene)> {
-    let ran = world.resource_scope(|world, named_system_registry: Mut<NamedSystemRegistry>| {
-        to_run.0.iter().map(|(_, func, _, _, _)|
-            named_system_registry.call::<(), HTMLScene>(world, func.0.as_str(), ()).unwrap()
-        ).collect::<Vec<_>>()
-    });
-    to_run.0.into_iter().zip(ran.into_iter()).collect()
+) -> Vec<(Entity, HTMLResponse)> {
+    let mut responses = Vec::new();
+
+    for ToRun { swap, indicator, request } in to_run.0 {
+        let trigger = request.trigger;
+
+        if let Some(emit) = world.get::<XEmit>(trigger).cloned() {
+            if let Err(error) = send_x_emit(world, &emit) {
+                responses.extend(error_response(world.get::<XErrorTarget>(trigger), &error).map(|response| (trigger, response)));
+                world.send_event(HtmlError { trigger, error });
+            }
+        }
+
+        if let Some(func) = world.get::<XFunction>(trigger).cloned() {
+            responses.extend(run_x_function(world, &func, request.clone(), swap.clone(), indicator).map(|response| (trigger, response)));
+        }
+
+        let requests = world.get::<XGet>(trigger).map(|get| (false, get.0.clone())).into_iter()
+            .chain(world.get::<XPost>(trigger).map(|post| (true, post.0.clone())));
+        for (post, url) in requests {
+            #[cfg(feature = "http")]
+            {
+                match http::form_values(world, trigger) {
+                    Ok(values) => {
+                        let task = http::request(url, post, values);
+                        start_task(world, PendingTask { trigger, target: request.target, swap: swap.clone(), indicator, task });
+                    },
+                    Err(error) => {
+                        let error = format!("Request to {url}: Failed to encode form: {error}");
+                        responses.extend(error_response(world.get::<XErrorTarget>(trigger), &error).map(|response| (trigger, response)));
+                        world.send_event(HtmlError { trigger, error });
+                    }
+                }
+            }
+            #[cfg(not(feature = "http"))]
+            warn!("Ignoring request to {url} (post: {post}), XGet and XPost require the `http` feature");
+        }
+
+        if let Some(route) = world.get::<XRoute>(trigger).cloned() {
+            world.resource_mut::<HtmlRouter>().navigate(&route);
+        }
+    }
+
+    responses
+}
+
+fn poll_tasks(
+    mut pending_tasks: ResMut<PendingTasks>,
+    entities: &Entities,
+    mut visibilities: Query<&mut Visibility>,
+    error_targets: Query<&XErrorTarget>,
+    mut errors: EventWriter<HtmlError>
+) -> Vec<(Entity, HTMLResponse)> {
+    let mut responses = Vec::new();
+
+    for pending in std::mem::take(&mut pending_tasks.0) {
+        let cancelled = !entities.contains(pending.trigger) || !entities.contains(pending.target);
+        if !cancelled && !pending.task.is_finished() {
+            pending_tasks.0.push(pending);
+            continue;
+        }
+
+        if let Some(mut indicator) = pending.indicator.and_then(|indicator| visibilities.get_mut(indicator).ok()) {
+            *indicator = Visibility::Hidden;
+        }
+        // Dropping the task cancels it
+        if cancelled {
+            continue;
+        }
+
+        match block_on(pending.task) {
+            Ok(scene) => responses.push((pending.trigger, HTMLResponse::default().swap(XTarget::Entity(pending.target), pending.swap, scene))),
+            Err(error) => {
+                let error = format!("HTMLTask failed: {error}");
+                if let Some(response) = error_response(error_targets.get(pending.trigger).ok(), &error) {
+                    responses.push((pending.trigger, response));
+                }
+                errors.send(HtmlError { trigger: pending.trigger, error });
+            }
+        }
+    }
+
+    responses
+}
+
+// A swap waiting out its XSwapModifiers delay
+struct DelayedSwap {
+    timer: Timer,
+    trigger: Entity,
+    target: Entity,
+    swap: XSwap,
+    scene: HTMLScene,
+    modifiers: XSwapModifiers
+}
+#[derive(Resource, Default)]
+struct DelayedSwaps(Vec<DelayedSwap>);
+
+// Swaps that failed or whose target couldn't be found, with their trigger, sent as HtmlError by report_swap_errors
+#[derive(Resource, Default)]
+struct SwapErrors(Vec<(Entity, String)>);
+
+fn report_swap_errors(
+    mut swap_errors: ResMut<SwapErrors>,
+    error_targets: Query<&XErrorTarget>,
+    targets: XTargets,
+    mut errors: EventWriter<HtmlError>
+) -> Vec<(Entity, HTMLResponse)> {
+    let mut responses = Vec::new();
+    for (trigger, error) in std::mem::take(&mut swap_errors.0) {
+        // Resolved up front, so a missing error target is only reported once instead of failing its own swap again
+        let error_target = error_targets.get(trigger).ok()
+            .and_then(|error_target| targets.resolve(trigger, &error_target.0).ok())
+            .map(|entity| XErrorTarget(XTarget::Entity(entity)));
+        responses.extend(error_response(error_target.as_ref(), &error).map(|response| (trigger, response)));
+        errors.send(HtmlError { trigger, error });
+    }
+    responses
 }
 
 fn swap_system(
-    to_run: In<Vec<(ToRun, HTMLScene)>>,
+    responses: In<Vec<(Entity, HTMLResponse)>>,
     mut html_scenes: ResMut<Assets<HTMLScene>>,
-    name_query: Query<(Entity, &Name)>,
-    children: Query<&Children>,
-    parents: Query<&Parent>,
+    mut delayed_swaps: ResMut<DelayedSwaps>,
+    mut swap_errors: ResMut<SwapErrors>,
+    modifiers: Query<&XSwapModifiers>,
+    error_targets: Query<&XErrorTarget>,
+    mut errors: EventWriter<HtmlError>,
+    targets: XTargets,
     mut commands: Commands
 ) {
-    for ((entity, _, _, swap, target), xs) in to_run.0.into_iter() {
-        let entity = match target {
-            XTarget::This => entity,
-            XTarget::Name(name) => name_query.iter().find(|(_, n)| n.as_str() == name).unwrap().0,
-            XTarget::ChildName(name) => children.iter_descendants(entity)
-                                            .find(|d| name_query.get(*d).map(|(_, n)| n.as_str() == name).unwrap_or(false)).unwrap(),
-            XTarget::Root => parents.iter_ancestors(entity).find(|a| !parents.contains(*a)).unwrap(),
-            _ => unimplemented!()
+    let mut swaps = Vec::new();
+    for (trigger, response) in responses.0 {
+        let response = match response.with_out_of_band() {
+            Ok(response) => response,
+            Err(error) => {
+                let response = error_response(error_targets.get(trigger).ok(), &error).unwrap_or_default();
+                errors.send(HtmlError { trigger, error });
+                response
+            }
         };
+        swaps.extend(response.0.into_iter().map(|swap| (trigger, swap)));
+    }
+    for (trigger, (target, swap, xs)) in swaps {
+        let entity = match targets.resolve(trigger, &target) {
+            Ok(entity) => entity,
+            Err(error) => {
+                swap_errors.0.push((trigger, error));
+                continue;
+            }
+        };
+        let modifiers = modifiers.get(trigger).cloned().unwrap_or_default();
+        if modifiers.delay <= 0. {
+            if let Err(error) = apply_swap(trigger, entity, swap, xs, &modifiers, &mut html_scenes, &targets, &mut commands) {
+                swap_errors.0.push((trigger, error));
+            }
+            continue;
+        }
+
+        // Mark the content that's about to be swapped out
+        let swapping = HtmlSwapping::new(modifiers.delay, modifiers.transition);
         match swap {
-            XSwap::Outer => {
-                commands.entity(entity)
-                    .despawn_descendants()
-                    .remove::<HTMLSceneInstance>()
-                    .insert(html_scenes.add(xs));
+            XSwap::Outer | XSwap::Morph | XSwap::Attributes | XSwap::Delete => {
+                commands.entity(entity).insert(swapping);
             },
-            XSwap::Inner => {
-                let child = commands.spawn_empty()
-                    .remove::<HTMLSceneInstance>()
-                    .insert(html_scenes.add(xs))
-                    .id();
-                commands.entity(entity)
-                    .despawn_descendants()
-                    .add_child(child);
+            XSwap::Inner => for child in targets.children.get(targets.slot(entity)).into_iter().flatten() {
+                commands.entity(*child).insert(swapping.clone());
             },
-            XSwap::Back => {
-                let child = commands.spawn_empty()
-                    .remove::<HTMLSceneInstance>()
-                    .insert(html_scenes.add(xs))
-                    .id();
-                commands.entity(entity)
-                    .push_children(&[child]);
-            },
-            XSwap::Front => {
-                let child = commands.spawn_empty()
-                    .remove::<HTMLSceneInstance>()
-                    .insert(html_scenes.add(xs))
-                    .id();
-                commands.entity(entity)
-                    .insert_children(0, &[child]);
-            }
+            _ => ()
+        }
+        delayed_swaps.0.push(DelayedSwap {
+            timer: Timer::from_seconds(modifiers.delay, TimerMode::Once),
+            trigger,
+            target: entity,
+            swap,
+            scene: xs,
+            modifiers
+        });
+    }
+}
+
+fn delayed_swap_system(
+    time: Res<Time>,
+    mut delayed_swaps: ResMut<DelayedSwaps>,
+    mut html_scenes: ResMut<Assets<HTMLScene>>,
+    mut swap_errors: ResMut<SwapErrors>,
+    entities: &Entities,
+    targets: XTargets,
+    mut commands: Commands
+) {
+    for delayed in delayed_swaps.0.iter_mut() {
+        delayed.timer.tick(time.delta());
+    }
+    let (ready, waiting) = std::mem::take(&mut delayed_swaps.0).into_iter()
+        .partition(|delayed| delayed.timer.finished());
+    delayed_swaps.0 = waiting;
+
+    for DelayedSwap { trigger, target, swap, scene, modifiers, .. } in ready {
+        // The target was despawned while waiting
+        if !entities.contains(target) {
+            continue;
+        }
+        if let Err(error) = apply_swap(trigger, target, swap, scene, &modifiers, &mut html_scenes, &targets, &mut commands) {
+            swap_errors.0.push((trigger, error));
         }
     }
 }
 
+// Deferred parts of a swap report their failures through SwapErrors
+fn apply_swap(
+    trigger: Entity,
+    entity: Entity,
+    swap: XSwap,
+    xs: HTMLScene,
+    modifiers: &XSwapModifiers,
+    html_scenes: &mut Assets<HTMLScene>,
+    targets: &XTargets,
+    commands: &mut Commands
+) -> Result<(), String> {
+    // The entity the incoming content is spawned into
+    let incoming = match swap {
+        XSwap::Outer => {
+            commands.add(move |world: &mut World| {
+                restore_transition(world, entity);
+                preserve_descendants(world, entity, entity);
+            });
+            commands.entity(entity)
+                .despawn_descendants()
+                .remove::<HTMLSceneInstance>()
+                .insert(html_scenes.add(xs));
+            Some(entity)
+        },
+        XSwap::Inner => {
+            let entity = targets.slot(entity);
+            let child = commands.spawn_empty()
+                .remove::<HTMLSceneInstance>()
+                .insert(html_scenes.add(xs))
+                .id();
+            commands.add(move |world: &mut World| preserve_descendants(world, entity, child));
+            commands.entity(entity)
+                .despawn_descendants()
+                .add_child(child);
+            Some(child)
+        },
+        XSwap::Back => {
+            let entity = targets.slot(entity);
+            let child = commands.spawn_empty()
+                .remove::<HTMLSceneInstance>()
+                .insert(html_scenes.add(xs))
+                .id();
+            commands.entity(entity)
+                .push_children(&[child]);
+            Some(child)
+        },
+        XSwap::Front => {
+            let entity = targets.slot(entity);
+            let child = commands.spawn_empty()
+                .remove::<HTMLSceneInstance>()
+                .insert(html_scenes.add(xs))
+                .id();
+            commands.entity(entity)
+                .insert_children(0, &[child]);
+            Some(child)
+        },
+        XSwap::BeforeBegin | XSwap::AfterEnd => {
+            let parent = targets.parents.get(entity)
+                .map_err(|_| format!("Can't swap {swap:?} a root element, it has no parent"))?
+                .get();
+            let index = targets.children.get(parent).unwrap().iter().position(|c| *c == entity).unwrap();
+            let index = if matches!(swap, XSwap::AfterEnd) { index + 1 } else { index };
+            let sibling = commands.spawn(html_scenes.add(xs)).id();
+            commands.entity(parent)
+                .insert_children(index, &[sibling]);
+            Some(sibling)
+        },
+        XSwap::Attributes => {
+            commands.add(move |world: &mut World| {
+                restore_transition(world, entity);
+                if let Err(error) = spawn_scene(&xs, entity, world, false) {
+                    world.resource_mut::<SwapErrors>().0.push((trigger, format!("Failed to apply attributes: {error}")));
+                }
+            });
+            Some(entity)
+        },
+        XSwap::Morph => {
+            commands.add(move |world: &mut World| {
+                restore_transition(world, entity);
+                if let Err(error) = morph_scene(&xs, entity, world) {
+                    world.resource_mut::<SwapErrors>().0.push((trigger, format!("Failed to morph: {error}")));
+                }
+            });
+            Some(entity)
+        },
+        XSwap::Delete => {
+            commands.entity(entity).despawn_recursive();
+            None
+        },
+        XSwap::None => None
+    };
+
+    if let Some(incoming) = incoming.filter(|_| modifiers.settle > 0.) {
+        commands.entity(incoming).insert(HtmlSettling::new(modifiers.settle, modifiers.transition));
+    }
+    Ok(())
+}
+
 pub struct XPlugin;
 impl Plugin for XPlugin {
     fn build(&self, app: &mut App) {
@@ -145,9 +1110,161 @@ impl Plugin for XPlugin {
             .register_type::<XTarget>()
             .register_type::<XFunction>()
             .register_type::<XOn>()
+            .register_type::<XOnModifiers>()
+            .register_type::<Seconds>()
+            .register_type::<KeyModifiers>()
+            .register_type::<XRevealed>()
+            .register_type::<XOnState>()
+            .register_type::<XArgs>()
+            .register_type::<HtmlRequest>()
+            .register_type::<HTMLResponse>()
+            .register_type::<HTMLTask>()
+            .register_type::<XErrorTarget>()
+            .register_type::<()>()
+            .register_type::<HTMLScene>()
+            .register_type::<Option<HTMLScene>>()
+            .register_type::<Option<HTMLResponse>>()
+            .register_type::<Option<HTMLTask>>()
+            .register_type::<Result<(), String>>()
+            .register_type::<Result<HTMLScene, String>>()
+            .register_type::<Result<HTMLResponse, String>>()
+            .register_type::<Result<HTMLTask, String>>()
+            .register_type_data::<(), ReflectIntoHTMLOutput>()
+            .register_type_data::<HTMLScene, ReflectIntoHTMLOutput>()
+            .register_type_data::<HTMLResponse, ReflectIntoHTMLOutput>()
+            .register_type_data::<HTMLTask, ReflectIntoHTMLOutput>()
+            .register_type_data::<Option<HTMLScene>, ReflectIntoHTMLOutput>()
+            .register_type_data::<Option<HTMLResponse>, ReflectIntoHTMLOutput>()
+            .register_type_data::<Option<HTMLTask>, ReflectIntoHTMLOutput>()
+            .register_type_data::<Result<(), String>, ReflectIntoHTMLOutput>()
+            .register_type_data::<Result<HTMLScene, String>, ReflectIntoHTMLOutput>()
+            .register_type_data::<Result<HTMLResponse, String>, ReflectIntoHTMLOutput>()
+            .register_type_data::<Result<HTMLTask, String>, ReflectIntoHTMLOutput>()
+            .register_type::<XIndicator>()
+            .register_type::<XGet>()
+            .register_type::<XPost>()
+            .register_type::<XName>()
+            .register_type::<XForm>()
+            .register_type::<XEmit>()
+            .register_type_data::<Text, ReflectFormValue>()
+            .register_type::<XPreserve>()
+            .register_type::<XSwapModifiers>()
+            .register_type::<SwapTransition>()
+            .register_type::<HtmlSwapping>()
+            .register_type::<HtmlSettling>()
+
+            .register_type::<XRoute>()
+            .register_type::<XOutlet>()
+
+            .add_event::<HtmlError>()
+            .init_resource::<PendingTasks>()
+            .init_resource::<DelayedSwaps>()
+            .init_resource::<SwapErrors>()
+            .init_resource::<HtmlRouter>()
             
             .add_systems(PreUpdate,
-                (find_to_run.pipe(run_x_funcs).pipe(swap_system), apply_deferred).before(spawn_scene_system)
-            );
+                (
+                    read_trigger_values, find_to_run.pipe(run_x_funcs).pipe(swap_system), poll_tasks.pipe(swap_system), delayed_swap_system, apply_deferred,
+                    report_swap_errors.pipe(swap_system), router_back_button, render_routes.pipe(swap_system)
+                )
+                    .chain()
+                    .before(spawn_scene_system)
+            )
+            .add_systems(Update, swap_phases);
+
+        app.world.resource::<AppTypeRegistry>().write()
+            .get_mut(TypeId::of::<XFunction>()).unwrap()
+            .insert(ReflectConstruct { func: construct_x_function });
+        app.world.resource::<AppTypeRegistry>().write()
+            .get_mut(TypeId::of::<XEmit>()).unwrap()
+            .insert(ReflectConstruct { func: construct_x_emit });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn out_of_band(html: &str) -> Result<Vec<(String, String, String)>, String> {
+        let response = HTMLResponse::default().swap(XTarget::This, XSwap::Outer, HTMLScene::try_from(html).unwrap());
+        Ok(response.with_out_of_band()?.0.into_iter()
+            .map(|(target, swap, scene)| (format!("{target:?}"), format!("{swap:?}"), scene.to_string()))
+            .collect())
+    }
+
+    fn seconds(seconds: f32) -> Duration {
+        Duration::from_secs_f32(seconds)
+    }
+
+    // Updates the state once per (triggered, value, seconds since the last update), returning whether each fired
+    fn fired(modifiers: XOnModifiers, updates: &[(bool, &str, f32)]) -> Vec<bool> {
+        let mut state = XOnState::default();
+        updates.iter()
+            .map(|(triggered, value, delta)| state.update(&modifiers, *triggered, value.to_string(), seconds(*delta)))
+            .collect()
+    }
+
+    #[test]
+    fn triggers_pass_through_without_modifiers() {
+        assert_eq!(fired(XOnModifiers::default(), &[(true, "", 0.), (false, "", 0.), (true, "", 0.)]), [true, false, true]);
+    }
+
+    #[test]
+    fn once_only_fires_the_first_time() {
+        let modifiers = XOnModifiers { once: true, ..default() };
+        assert_eq!(fired(modifiers, &[(true, "", 0.), (true, "", 0.), (true, "", 1.)]), [true, false, false]);
+    }
+
+    #[test]
+    fn changed_fires_when_the_value_changes() {
+        let modifiers = XOnModifiers { changed: true, ..default() };
+        let updates = [(true, "a", 0.), (true, "a", 0.), (false, "b", 0.), (true, "b", 0.), (true, "a", 0.)];
+        assert_eq!(fired(modifiers, &updates), [true, false, false, true, true]);
+    }
+
+    #[test]
+    fn delay_waits_until_the_trigger_is_quiet() {
+        let modifiers = XOnModifiers { delay: Seconds(0.3), ..default() };
+        let updates = [(true, "", 0.), (false, "", 0.2), (true, "", 0.), (false, "", 0.2), (false, "", 0.2), (false, "", 0.2)];
+        assert_eq!(fired(modifiers, &updates), [false, false, false, false, true, false]);
+    }
+
+    #[test]
+    fn throttle_ignores_triggers_after_firing() {
+        let modifiers = XOnModifiers { throttle: Seconds(1.), ..default() };
+        let updates = [(true, "", 0.), (true, "", 0.5), (true, "", 0.6), (true, "", 0.1)];
+        assert_eq!(fired(modifiers, &updates), [true, false, true, false]);
+    }
+
+    #[test]
+    fn seconds_are_written_like_htmx() {
+        assert_eq!(Seconds::try_from("300ms".to_string()), Ok(Seconds(0.3)));
+        assert_eq!(Seconds::try_from("1.5s".to_string()), Ok(Seconds(1.5)));
+        assert!(Seconds::try_from("0.3".to_string()).is_err());
+    }
+
+    #[test]
+    fn out_of_band_elements_are_swapped_by_id() {
+        let swaps = out_of_band(
+            r#"<Node id="number"></Node><Node id="log" x-swap-oob="Back"><Text>One</Text><Text>Two</Text></Node><Text id="status" x-swap-oob>Saved</Text>"#
+        ).unwrap();
+        assert_eq!(swaps, [
+            ("This", "Outer", r#"<Node id="number"></Node>"#),
+            (r#"Name("log")"#, "Back", "<Text>One</Text>"),
+            (r#"Name("log")"#, "Back", "<Text>Two</Text>"),
+            (r#"Name("status")"#, "Outer", r#"<Text id="status">Saved</Text>"#)
+        ].map(|(target, swap, scene)| (target.to_string(), swap.to_string(), scene.to_string())));
+    }
+
+    #[test]
+    fn out_of_band_element_without_id_fails() {
+        let error = out_of_band(r#"<Node id="number"></Node><Node x-swap-oob="Back"></Node>"#).unwrap_err();
+        assert_eq!(error, "Out of band swap element <Node> is missing an id");
+    }
+
+    #[test]
+    fn invalid_out_of_band_swap_fails() {
+        let error = out_of_band(r#"<Node id="log" x-swap-oob="Sideways"></Node>"#).unwrap_err();
+        assert!(error.starts_with("Invalid x-swap-oob value [Sideways]"), "{error}");
     }
-}
\ No newline at end of file
+}
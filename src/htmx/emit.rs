@@ -0,0 +1,122 @@
+use std::fmt::{self, Formatter};
+
+use bevy::prelude::*;
+use bevy::ecs::reflect::ReflectComponent;
+use bevy::reflect::{DynamicStruct, FromType, TypeInfo, erased_serde};
+use serde::{Deserializer, de::{DeserializeSeed, Visitor, SeqAccess, MapAccess, Error}};
+
+use crate::typed_partial_reflect_deserializer::TypedPartialReflectDeserializer;
+use super::XArgs;
+
+// Event sent when the element's XOn trigger fires, written as the event type's name and its RON fields,
+// e.g. XEmit="StartGame" or XEmit="OpenInventory(tab: 2)"
+#[derive(Component, Debug, Clone, Reflect)]
+#[reflect(Component)]
+pub struct XEmit(pub XArgs);
+
+// Allows an event type to be sent from XEmit
+#[derive(Clone)]
+pub struct ReflectHtmlEvent {
+    pub func: fn(world: &mut World, event: &dyn Reflect) -> Result<(), String>
+}
+impl<T: Event + FromReflect> FromType<T> for ReflectHtmlEvent {
+    fn from_type() -> Self {
+        Self {
+            func: |world: &mut World, event: &dyn Reflect| {
+                // Fields left out of the payload need #[reflect(Default)] on the event
+                let event = T::from_reflect(event)
+                    .ok_or_else(|| format!("Event [{}]: Failed to convert XEmit payload, are fields missing?", std::any::type_name::<T>()))?;
+                world.send_event(event);
+                Ok(())
+            }
+        }
+    }
+}
+impl ReflectHtmlEvent {
+    pub fn send(&self, world: &mut World, event: &dyn Reflect) -> Result<(), String> {
+        (self.func)(world, event)
+    }
+}
+
+pub(crate) fn send_x_emit(world: &mut World, emit: &XEmit) -> Result<(), String> {
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+    let type_path = emit.0.0.get_represented_type_info().map(|info| info.type_path()).unwrap_or_default();
+    let html_event = type_registry.get_with_type_path(type_path)
+        .and_then(|registration| registration.data::<ReflectHtmlEvent>())
+        .ok_or_else(|| format!("Event [{type_path}]: Missing ReflectHtmlEvent type data"))?
+        .clone();
+    drop(type_registry);
+    html_event.send(world, &*emit.0.0)
+}
+
+struct XEmitSeed<'a>(&'a mut World);
+impl<'a, 'de> DeserializeSeed<'de> for XEmitSeed<'a> {
+    type Value = XEmit;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de> {
+        deserializer.deserialize_any(self)
+    }
+}
+impl<'a, 'de> Visitor<'de> for XEmitSeed<'a> {
+    type Value = XEmit;
+
+    fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str("event type name, optionally followed by its fields")
+    }
+
+    // Unit struct events
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: Error, {
+        let type_registry = self.0.resource::<AppTypeRegistry>().clone();
+        let type_registry = type_registry.read();
+        let registration = type_registry.get_with_short_type_path(value)
+            .filter(|registration| registration.data::<ReflectHtmlEvent>().is_some())
+            .ok_or_else(|| Error::custom(format_args!("Event [{value}]: Not registered with ReflectHtmlEvent")))?;
+
+        let TypeInfo::Struct(_) = registration.type_info() else {
+            return Err(Error::custom(format_args!("Event [{value}]: Expected fields")));
+        };
+        let mut event = DynamicStruct::default();
+        event.set_represented_type(Some(registration.type_info()));
+        Ok(XEmit(XArgs(Box::new(event))))
+    }
+
+    // The tuple struct wrapper around the event
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>, {
+        seq.next_element_seed(self)?
+            .ok_or_else(|| Error::custom("Missing event"))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>, {
+        let name: String = map.next_key()?
+            .ok_or_else(|| Error::custom("Missing event type name"))?;
+
+        let type_registry = self.0.resource::<AppTypeRegistry>().clone();
+        let type_registry = type_registry.read();
+        let registration = type_registry.get_with_short_type_path(&name)
+            .filter(|registration| registration.data::<ReflectHtmlEvent>().is_some())
+            .ok_or_else(|| Error::custom(format_args!("Event [{name}]: Not registered with ReflectHtmlEvent")))?;
+
+        map.next_value_seed(TypedPartialReflectDeserializer::new(self.0, registration, &type_registry, true))
+            .map(|event| XEmit(XArgs(event)))
+    }
+}
+
+// Failures are logged like XFunction's, since the fallback deserialization only reports a generic error
+pub(crate) fn construct_x_emit(world: &mut World, deserializer: &mut dyn erased_serde::Deserializer) -> Option<Box<dyn Reflect>> {
+    match XEmitSeed(world).deserialize(deserializer) {
+        Ok(emit) => Some(Box::new(emit)),
+        Err(error) => {
+            error!("XEmit: {error}");
+            None
+        }
+    }
+}
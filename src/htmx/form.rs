@@ -0,0 +1,119 @@
+use std::any::TypeId;
+
+use bevy::prelude::*;
+use bevy::ecs::reflect::ReflectComponent;
+use bevy::reflect::{DynamicStruct, FromType, TypeRegistry, serde::TypedReflectSerializer};
+use serde::{Serialize, Deserialize, de::DeserializeSeed};
+
+use crate::typed_partial_reflect_deserializer::TypedPartialReflectDeserializer;
+use super::XName;
+
+// Element whose XName descendants are submitted together with any trigger inside it
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize, Default)]
+pub struct XForm;
+
+// Components that hold an input's value, e.g. the text of a text field or the state of a toggle
+pub trait FormValue {
+    fn form_value(&self) -> Box<dyn Reflect>;
+}
+impl FormValue for Text {
+    fn form_value(&self) -> Box<dyn Reflect> {
+        Box::new(self.sections.iter().map(|section| section.value.as_str()).collect::<String>())
+    }
+}
+
+#[derive(Clone)]
+pub struct ReflectFormValue {
+    pub func: fn(this: &dyn Reflect) -> Box<dyn Reflect>
+}
+impl<T: FormValue + Reflect> FromType<T> for ReflectFormValue {
+    fn from_type() -> Self {
+        Self {
+            func: |this: &dyn Reflect| -> Box<dyn Reflect> {
+                this.downcast_ref::<T>().unwrap().form_value()
+            }
+        }
+    }
+}
+impl ReflectFormValue {
+    pub fn form_value(&self, this: &dyn Reflect) -> Box<dyn Reflect> {
+        (self.func)(this)
+    }
+}
+
+// The component an input's value is taken from, when several have ReflectFormValue type data.
+// Text is only used if nothing else holds a value, since custom inputs usually show one too,
+// and remaining ties go by type path.
+fn input_value(world: &World, type_registry: &TypeRegistry, entity: Entity) -> Option<Box<dyn Reflect>> {
+    let entity_ref = world.entity(entity);
+    type_registry.iter()
+        .filter(|registration| registration.data::<ReflectFormValue>().is_some())
+        .filter_map(|registration| Some((registration, registration.data::<ReflectComponent>()?.reflect(entity_ref)?)))
+        .min_by_key(|(registration, _)| (registration.type_id() == TypeId::of::<Text>(), registration.type_info().type_path()))
+        .map(|(registration, component)| registration.data::<ReflectFormValue>().unwrap().form_value(component))
+}
+
+// An input's value as text, strings as they are and anything else as RON
+pub(crate) fn value_text(type_registry: &TypeRegistry, value: &dyn Reflect) -> Result<String, String> {
+    match value.downcast_ref::<String>() {
+        Some(value) => Ok(value.clone()),
+        None => ron::to_string(&TypedReflectSerializer::new(value, type_registry)).map_err(|e| e.to_string())
+    }
+}
+
+// Value of the element's input as text, or of the first input inside it, e.g. a button's Text
+pub(crate) fn element_value(world: &World, type_registry: &TypeRegistry, entity: Entity) -> Option<String> {
+    let mut to_visit = vec![entity];
+    while let Some(entity) = to_visit.pop() {
+        if let Some(value) = input_value(world, type_registry, entity) {
+            return value_text(type_registry, &*value).ok();
+        }
+        if let Some(children) = world.get::<Children>(entity) {
+            to_visit.extend(children.iter().rev());
+        }
+    }
+    None
+}
+
+// Values of the named inputs in the trigger's enclosing XForm, if there is one
+pub(crate) fn collect_form(world: &World, type_registry: &TypeRegistry, trigger: Entity) -> Option<DynamicStruct> {
+    let form = std::iter::successors(Some(trigger), |entity| world.get::<Parent>(*entity).map(|parent| parent.get()))
+        .find(|entity| world.get::<XForm>(*entity).is_some())?;
+
+    let mut values = DynamicStruct::default();
+    let mut to_visit = vec![form];
+    while let Some(entity) = to_visit.pop() {
+        if let Some(name) = world.get::<XName>(entity) {
+            if let Some(value) = input_value(world, type_registry, entity) {
+                values.insert_boxed(&name.0, value);
+            }
+        }
+        if let Some(children) = world.get::<Children>(entity) {
+            to_visit.extend(children.iter().rev());
+        }
+    }
+    Some(values)
+}
+
+// Converts a form into a named system's input, either as is or deserialized into a reflected struct
+pub(crate) fn form_input(
+    world: &mut World, type_registry: &TypeRegistry, form: DynamicStruct, in_type: TypeId
+) -> Result<Box<dyn Reflect>, String> {
+    if in_type == TypeId::of::<DynamicStruct>() {
+        return Ok(Box::new(form));
+    }
+
+    let mut fields = Vec::new();
+    for (i, value) in form.iter_fields().enumerate() {
+        let value = ron::to_string(&TypedReflectSerializer::new(value, type_registry)).map_err(|e| e.to_string())?;
+        fields.push(format!("{}: {value}", form.name_at(i).unwrap()));
+    }
+    let ron = format!("({})", fields.join(", "));
+
+    let registration = type_registry.get(in_type).ok_or("Input type not in type registry")?;
+    let mut ron_de = ron::Deserializer::from_str(&ron).map_err(|e| e.to_string())?;
+    TypedPartialReflectDeserializer::new(world, registration, type_registry, true)
+        .deserialize(&mut ron_de)
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,115 @@
+use bevy::prelude::*;
+use bevy::tasks::{IoTaskPool, Task};
+
+use crate::HTMLScene;
+use super::{collect_form, value_text};
+
+// The trigger's form as text values
+pub(super) fn form_values(world: &World, trigger: Entity) -> Result<Vec<(String, String)>, String> {
+    let type_registry = world.resource::<AppTypeRegistry>().read();
+    let Some(form) = collect_form(world, &type_registry, trigger) else { return Ok(Vec::new()); };
+    form.iter_fields().enumerate()
+        .map(|(i, value)| Ok((form.name_at(i).unwrap().to_string(), value_text(&type_registry, value)?)))
+        .collect()
+}
+
+// Sends the request on the IO task pool, parsing the response body as a scene
+pub(super) fn request(url: String, post: bool, values: Vec<(String, String)>) -> Task<Result<HTMLScene, String>> {
+    IoTaskPool::get().spawn(async move {
+        let values: Vec<(&str, &str)> = values.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+        let response = if post {
+            ureq::post(&url).send_form(&values)
+        } else {
+            values.iter().fold(ureq::get(&url), |request, (name, value)| request.query(name, value)).call()
+        }.map_err(|e| format!("Request to {url} failed: {e}"))?;
+
+        let body = response.into_string().map_err(|e| format!("Request to {url} failed: {e}"))?;
+        HTMLScene::try_from(body).map_err(|e| format!("Response from {url} isn't valid HTML: {e}"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+
+    use bevy::tasks::{TaskPool, block_on};
+
+    use super::*;
+
+    fn read_request(stream: &mut TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        loop {
+            let len = stream.read(&mut buffer).unwrap();
+            request.extend_from_slice(&buffer[..len]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            let complete = text.split_once("\r\n\r\n").is_some_and(|(head, body)| {
+                let length = head.lines()
+                    .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|length| length.trim().parse().unwrap()))
+                    .unwrap_or(0);
+                body.len() >= length
+            });
+            if complete || len == 0 {
+                return text;
+            }
+        }
+    }
+
+    // Answers a single request on an ephemeral port, passing on what was requested
+    fn stub_server(status: &'static str, body: &'static str) -> (String, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/panel", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            sender.send(read_request(&mut stream)).unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 {status}\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            ).unwrap();
+        });
+        (url, receiver)
+    }
+
+    fn send(url: String, post: bool) -> Result<HTMLScene, String> {
+        IoTaskPool::get_or_init(TaskPool::new);
+        block_on(request(url, post, vec![("player".to_string(), "Alice Smith".to_string())]))
+    }
+
+    fn root(scene: &HTMLScene) -> &html_parser::Element {
+        scene.dom().children.first().and_then(|node| node.element()).unwrap()
+    }
+
+    #[test]
+    fn get_sends_query_and_parses_body() {
+        let (url, requests) = stub_server("200 OK", r#"<Node id="panel"><Text>Hello</Text></Node>"#);
+        let scene = send(url, false).unwrap();
+
+        assert!(requests.recv().unwrap().starts_with("GET /panel?player=Alice+Smith "));
+        assert_eq!(root(&scene).name, "Node");
+        assert_eq!(root(&scene).id.as_deref(), Some("panel"));
+        assert_eq!(root(&scene).children[0].element().unwrap().children[0].text(), Some("Hello"));
+    }
+
+    #[test]
+    fn post_sends_form_body() {
+        let (url, requests) = stub_server("200 OK", r#"<Text id="saved">Saved</Text>"#);
+        let scene = send(url, true).unwrap();
+
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("POST /panel "));
+        assert!(request.ends_with("player=Alice+Smith"));
+        assert_eq!(root(&scene).id.as_deref(), Some("saved"));
+    }
+
+    #[test]
+    fn error_status_fails() {
+        let (url, _requests) = stub_server("500 Internal Server Error", "Oops");
+        let error = send(url, false).unwrap_err();
+        assert!(error.starts_with("Request to http://127.0.0.1:"));
+    }
+}
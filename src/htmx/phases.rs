@@ -0,0 +1,168 @@
+use bevy::prelude::*;
+use bevy::ecs::reflect::ReflectComponent;
+use bevy::reflect::{ReflectDeserialize, std_traits::ReflectDefault};
+use serde::{Serialize, Deserialize};
+
+// Delays the trigger's swaps, and keeps the incoming content settling afterwards (both in seconds)
+// e.g. XSwapModifiers="delay: 0.2, settle: 0.3, transition: Fade"
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize, Default)]
+#[serde(default)]
+pub struct XSwapModifiers {
+    pub delay: f32,
+    pub settle: f32,
+    pub transition: SwapTransition
+}
+// Animation driven by the swapping and settling phases
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Deserialize, Default)]
+pub enum SwapTransition {
+    #[default]
+    None,
+    Fade,
+    Slide(f32, f32) // Offset in logical pixels the content slides out to and in from
+}
+
+// Content that's about to be swapped out
+#[derive(Component, Debug, Clone, Reflect)]
+#[reflect(Component)]
+pub struct HtmlSwapping {
+    pub timer: Timer,
+    pub transition: SwapTransition
+}
+impl HtmlSwapping {
+    pub fn new(seconds: f32, transition: SwapTransition) -> Self {
+        Self { timer: Timer::from_seconds(seconds, TimerMode::Once), transition }
+    }
+}
+// Content that was just swapped in, removed once the timer finishes
+#[derive(Component, Debug, Clone, Reflect)]
+#[reflect(Component)]
+pub struct HtmlSettling {
+    pub timer: Timer,
+    pub transition: SwapTransition
+}
+impl HtmlSettling {
+    pub fn new(seconds: f32, transition: SwapTransition) -> Self {
+        Self { timer: Timer::from_seconds(seconds, TimerMode::Once), transition }
+    }
+}
+
+// Values from before a transition started, restored when it ends
+#[derive(Component, Clone)]
+pub(crate) struct TransitionBase {
+    background: Option<f32>,
+    text: Vec<f32>,
+    left: Val,
+    top: Val
+}
+
+fn offset(base: Val, offset: f32) -> Val {
+    match base {
+        Val::Auto => Val::Px(offset),
+        Val::Px(px) => Val::Px(px + offset),
+        val => val
+    }
+}
+
+// Puts back the values of a subtree's transition, e.g. before swapping new content into it
+pub(crate) fn restore_transition(world: &mut World, entity: Entity) {
+    let mut stack = vec![entity];
+    while let Some(entity) = stack.pop() {
+        let mut entity_mut = world.entity_mut(entity);
+        entity_mut.remove::<HtmlSwapping>();
+        if let Some(base) = entity_mut.take::<TransitionBase>() {
+            if let (Some(mut background), Some(alpha)) = (entity_mut.get_mut::<BackgroundColor>(), base.background) {
+                background.0.set_a(alpha);
+            }
+            if let Some(mut text) = entity_mut.get_mut::<Text>() {
+                for (section, alpha) in text.sections.iter_mut().zip(base.text) {
+                    section.style.color.set_a(alpha);
+                }
+            }
+            if let Some(mut style) = entity_mut.get_mut::<Style>() {
+                style.left = base.left;
+                style.top = base.top;
+            }
+        }
+        stack.extend(world.get::<Children>(entity).into_iter().flat_map(|c| c.iter().copied()));
+    }
+}
+
+pub(crate) fn swap_phases(
+    time: Res<Time>,
+    mut phases: Query<(Entity, Option<&mut HtmlSwapping>, Option<&mut HtmlSettling>), Or<(With<HtmlSwapping>, With<HtmlSettling>)>>,
+    children: Query<&Children>,
+    mut animated: Query<(Option<&mut BackgroundColor>, Option<&mut Text>, Option<&mut Style>, Option<&TransitionBase>)>,
+    mut commands: Commands
+) {
+    for (entity, swapping, settling) in phases.iter_mut() {
+        // Settling takes over from swapping on entities that are swapped into (e.g. Outer)
+        let (timer, transition, settles) = match (swapping, settling) {
+            (_, Some(settling)) => {
+                let settling = settling.into_inner();
+                (&mut settling.timer, settling.transition, true)
+            },
+            (Some(swapping), None) => {
+                let swapping = swapping.into_inner();
+                (&mut swapping.timer, swapping.transition, false)
+            },
+            (None, None) => unreachable!()
+        };
+        timer.tick(time.delta());
+        let t = timer.percent();
+        let t = t * t * (3. - 2. * t);
+        // How much of the content is shown
+        let shown = if settles { t } else { 1. - t };
+        let finished = timer.finished();
+
+        let subtree = match transition {
+            SwapTransition::None => Vec::new(),
+            SwapTransition::Fade => std::iter::once(entity).chain(children.iter_descendants(entity)).collect(),
+            SwapTransition::Slide(..) => vec![entity]
+        };
+        for entity in subtree {
+            let Ok((background, text, style, base)) = animated.get_mut(entity) else { continue; };
+            let base = match base {
+                Some(base) => base.clone(),
+                None => {
+                    let base = TransitionBase {
+                        background: background.as_ref().map(|b| b.0.a()),
+                        text: text.as_ref().map(|t| t.sections.iter().map(|s| s.style.color.a()).collect()).unwrap_or_default(),
+                        left: style.as_ref().map(|s| s.left).unwrap_or_default(),
+                        top: style.as_ref().map(|s| s.top).unwrap_or_default()
+                    };
+                    commands.entity(entity).insert(base.clone());
+                    base
+                }
+            };
+
+            match transition {
+                SwapTransition::Fade => {
+                    if let (Some(mut background), Some(alpha)) = (background, base.background) {
+                        background.0.set_a(alpha * shown);
+                    }
+                    if let Some(mut text) = text {
+                        for (section, alpha) in text.sections.iter_mut().zip(base.text.iter()) {
+                            section.style.color.set_a(alpha * shown);
+                        }
+                    }
+                },
+                SwapTransition::Slide(x, y) => {
+                    if let Some(mut style) = style {
+                        style.left = offset(base.left, x * (1. - shown));
+                        style.top = offset(base.top, y * (1. - shown));
+                    }
+                },
+                SwapTransition::None => ()
+            }
+            if finished && settles {
+                commands.entity(entity).remove::<TransitionBase>();
+            }
+        }
+
+        if finished && settles {
+            commands.entity(entity).remove::<HtmlSettling>();
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::any::TypeId;
+use std::fmt::Display;
+
+use bevy::prelude::*;
+use bevy::reflect::{Enum, FromType, TypeInfo, TypeRegistry, ReflectFromReflect, ReflectRef, VariantInfo};
+
+use crate::named_system_registry::{NamedSystemRegistry, NamedSystemError};
+
+// What a stage's output means for the rest of a pipeline
+pub enum PipeOutput {
+    Continue(Box<dyn Reflect>),
+    Stop,
+    Fail(String)
+}
+// Outputs that are unwrapped before being passed to the next stage, registered as ReflectPipeOutput type data.
+// Options don't need to be registered.
+pub trait IntoPipeOutput {
+    type Inner: Reflect;
+    fn into_pipe_output(self) -> PipeOutput;
+}
+impl<T: Reflect> IntoPipeOutput for Option<T> {
+    type Inner = T;
+    fn into_pipe_output(self) -> PipeOutput {
+        match self {
+            Some(value) => PipeOutput::Continue(Box::new(value)),
+            None => PipeOutput::Stop
+        }
+    }
+}
+impl<T: Reflect, E: Display> IntoPipeOutput for Result<T, E> {
+    type Inner = T;
+    fn into_pipe_output(self) -> PipeOutput {
+        match self {
+            Ok(value) => PipeOutput::Continue(Box::new(value)),
+            Err(error) => PipeOutput::Fail(error.to_string())
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ReflectPipeOutput {
+    pub inner: TypeId,
+    pub func: fn(this: Box<dyn Reflect>) -> PipeOutput
+}
+impl<T: IntoPipeOutput + Reflect> FromType<T> for ReflectPipeOutput {
+    fn from_type() -> Self {
+        Self {
+            inner: TypeId::of::<T::Inner>(),
+            func: |this: Box<dyn Reflect>| -> PipeOutput {
+                let this: T = *this.downcast().unwrap();
+                this.into_pipe_output()
+            }
+        }
+    }
+}
+impl ReflectPipeOutput {
+    pub fn into_pipe_output(&self, this: Box<dyn Reflect>) -> PipeOutput {
+        (self.func)(this)
+    }
+}
+
+// Named systems in a pipeline, e.g. "validate | save | render_saved"
+pub(crate) fn pipeline_stages(name: &str) -> Vec<&str> {
+    name.split('|').map(str::trim).collect()
+}
+
+fn option_inner(type_registry: &TypeRegistry, type_id: TypeId) -> Option<TypeId> {
+    match type_registry.get(type_id)?.type_info() {
+        TypeInfo::Enum(info) if info.type_path().starts_with("core::option::Option<") => match info.variant("Some")? {
+            VariantInfo::Tuple(some) => Some(some.field_at(0)?.type_id()),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+// The type a stage's output is passed on to the next stage as
+fn pipe_inner(type_registry: &TypeRegistry, out_type: TypeId) -> Option<TypeId> {
+    type_registry.get_type_data::<ReflectPipeOutput>(out_type)
+        .map(|pipe_output| pipe_output.inner)
+        .or_else(|| option_inner(type_registry, out_type))
+}
+
+fn into_pipe_output(type_registry: &TypeRegistry, value: Box<dyn Reflect>, in_type: TypeId) -> PipeOutput {
+    if value.as_any().type_id() == in_type {
+        return PipeOutput::Continue(value);
+    }
+    if let Some(pipe_output) = type_registry.get_type_data::<ReflectPipeOutput>(value.as_any().type_id()) {
+        return pipe_output.into_pipe_output(value);
+    }
+    // Unregistered options are unwrapped through reflection
+    let some = match value.reflect_ref() {
+        ReflectRef::Enum(option) if option.variant_name() == "None" => return PipeOutput::Stop,
+        ReflectRef::Enum(option) => option.field_at(0).map(|some| some.clone_value()),
+        _ => None
+    };
+    match some.and_then(|some| type_registry.get_type_data::<ReflectFromReflect>(in_type)?.from_reflect(&*some)) {
+        Some(some) => PipeOutput::Continue(some),
+        None => PipeOutput::Continue(value)
+    }
+}
+
+// Calls each stage with the previous stage's output, stopping early on None or Err.
+// Stopping returns (), so nothing is swapped.
+pub(crate) fn call_pipeline(world: &mut World, stages: &[&str], input: Box<dyn Reflect>) -> Result<Box<dyn Reflect>, String> {
+    // The registry is only read between stages, since named systems may register types
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+
+    // Check the whole pipeline before running any of it
+    let named_system_registry = world.resource::<NamedSystemRegistry>();
+    let mut in_types = Vec::new();
+    for stage in stages {
+        let (in_type, _) = named_system_registry.get_type_ids(stage)
+            .ok_or_else(|| NamedSystemError::NotFound { name: stage.to_string(), suggestions: named_system_registry.similar_names(stage) }.to_string())?;
+        in_types.push(in_type);
+    }
+    for (i, pair) in stages.windows(2).enumerate() {
+        let (_, out_type) = named_system_registry.get_type_ids(pair[0]).unwrap();
+        if out_type != in_types[i + 1] && pipe_inner(&type_registry.read(), out_type) != Some(in_types[i + 1]) {
+            return Err(NamedSystemError::WrongInput {
+                name: pair[1].to_string(),
+                expected: named_system_registry.get_type_paths(pair[1]).unwrap().0,
+                actual: named_system_registry.get_type_paths(pair[0]).unwrap().1.to_string()
+            }.to_string());
+        }
+    }
+
+    let mut value = input;
+    for (i, stage) in stages.iter().enumerate() {
+        value = NamedSystemRegistry::call_reflect(world, stage, value).map_err(|e| e.to_string())?;
+        let Some(next_in_type) = in_types.get(i + 1) else { break; };
+        let output = into_pipe_output(&type_registry.read(), value, *next_in_type);
+        value = match output {
+            PipeOutput::Continue(value) => value,
+            PipeOutput::Stop => return Ok(Box::new(())),
+            PipeOutput::Fail(error) => return Err(format!("Named system [{stage}]: {error}"))
+        };
+    }
+    Ok(value)
+}
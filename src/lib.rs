@@ -1,4 +1,4 @@
-use std::{borrow::Cow, fmt::Display, collections::HashMap};
+use std::{any::TypeId, borrow::Cow, fmt::{Display, Write}, collections::HashMap};
 
 use bevy::{prelude::*, reflect::{TypeInfo, TypeRegistry, TypeRegistration, FromType}, gltf::Gltf, asset::{AssetLoader, AsyncReadExt, embedded_asset}};
 use bevy::reflect::erased_serde;
@@ -11,8 +11,17 @@ use thiserror::Error;
 
 pub mod htmx;
 use htmx::*;
+pub mod router;
+pub mod scroll;
+pub mod focus;
+pub mod theme;
+use theme::*;
+pub mod transition;
+use transition::*;
+mod conditional_style;
+use conditional_style::*;
 mod named_system_registry;
-pub use named_system_registry::{NamedSystemRegistryExt, NamedSystemRegistry};
+pub use named_system_registry::{NamedSystemRegistryExt, NamedSystemRegistry, NamedSystemId, NamedSystemInfo, NamedSystemError};
 
 mod typed_partial_reflect_deserializer;
 use typed_partial_reflect_deserializer::*;
@@ -40,6 +49,43 @@ impl From<Markup> for HTMLScene {
         HTMLScene(value.clone().into_string(), Dom::parse(&value.into_string()).unwrap())
     }
 }
+impl From<Vec<html_parser::Node>> for HTMLScene {
+    fn from(nodes: Vec<html_parser::Node>) -> Self {
+        fn render(node: &html_parser::Node, out: &mut String) {
+            match node {
+                html_parser::Node::Text(text) => out.push_str(text),
+                html_parser::Node::Comment(comment) => { let _ = write!(out, "<!--{comment}-->"); },
+                html_parser::Node::Element(el) => {
+                    // Attribute values are kept entity-encoded by the parser, so only quotes need escaping
+                    let _ = write!(out, "<{}", el.name);
+                    if let Some(id) = el.id.as_ref() {
+                        let _ = write!(out, " id=\"{}\"", id.replace('"', "&quot;"));
+                    }
+                    if !el.classes.is_empty() {
+                        let _ = write!(out, " class=\"{}\"", el.classes.join(" ").replace('"', "&quot;"));
+                    }
+                    for (attribute, value) in el.attributes.iter() {
+                        match value {
+                            Some(value) => { let _ = write!(out, " {}=\"{}\"", attribute, value.replace('"', "&quot;")); },
+                            None => { let _ = write!(out, " {}", attribute); }
+                        }
+                    }
+                    out.push('>');
+                    for child in &el.children {
+                        render(child, out);
+                    }
+                    let _ = write!(out, "</{}>", el.name);
+                }
+            }
+        }
+
+        let mut source = String::new();
+        for node in &nodes {
+            render(node, &mut source);
+        }
+        HTMLScene(source, Dom { children: nodes, ..Default::default() })
+    }
+}
 impl TryFrom<&str> for HTMLScene {
     type Error = html_parser::Error;
 
@@ -134,6 +180,10 @@ impl ReflectIntoHTMLScene {
 pub enum HTMLSceneSpawnError {
     #[error("Attribute name [{0}]: Failed to deserialize")]
     DeserializationFailed(String),
+    #[error("Attribute name [{0}]: Referred to undefined component")]
+    UndefinedComponent(String),
+    #[error("Attribute name [{0}]: Missing ReflectComponent type data")]
+    MissingReflectComponent(String),
     #[error("Attribute name [{0}]: Invalid attribute associated type <{1}>")]
     InvalidAttributeAssociatedType(String, String),
     #[error("Attribute name [{0}]: Component doesn't implement/reflect Default")]
@@ -142,38 +192,47 @@ pub enum HTMLSceneSpawnError {
     NoDeserialize(String),
     #[error("Attribute name [{0}]: Attempting to patch a non-struct component")]
     PatchNonStruct(String),
+    #[error("Attribute name [{0}]: {1}")]
+    InvalidValue(String, String),
+    #[error("Theme variable [--{0}]: Not defined in HtmlTheme")]
+    UndefinedThemeVariable(String),
     #[error("Unrecognized tag name {0}")]
     UnrecognizedTagName(String)
 }
 
-fn construct_instance(world: &mut World, type_registry: &TypeRegistry, key_type: &TypeRegistration, value: Option<&str>) -> Result<Box<dyn Reflect>, HTMLSceneSpawnError> {
+// Deserializes an attribute value, only containing the fields it sets if the type has a default
+fn deserialize_instance(
+    world: &mut World, type_registry: &TypeRegistry, key_type: &TypeRegistration, value: &str
+) -> Result<Box<dyn Reflect>, HTMLSceneSpawnError> {
     let ron_options = Options::default();//.with_default_extension(Extensions::UNWRAP_NEWTYPES);
 
     let default_impl = type_registry.get_type_data::<ReflectDefault>(key_type.type_id());
+    let decoded_html_string = html_escape::decode_html_entities(value);
+    let decoded_html_string = resolve_theme_vars(world.get_resource::<HtmlTheme>(), &decoded_html_string)?;
+
+    // Wrap structs in parens for convenience
+    let decoded_html_string = match key_type.type_info() {
+        TypeInfo::Struct(_) | TypeInfo::TupleStruct(_) => {
+            Cow::Owned(format!("({})", decoded_html_string))
+        },
+        _ => decoded_html_string
+    };
 
-    let instance: Option<Box<dyn Reflect>> = if value.is_some() {
-        let decoded_html_string = html_escape::decode_html_entities(value.unwrap());
+    let deserialization_failed = || HTMLSceneSpawnError::DeserializationFailed(key_type.type_info().type_path().to_string());
+    let mut ron_de = ron::Deserializer::from_str_with_options(
+        &decoded_html_string, &ron_options
+    ).map_err(|_| deserialization_failed())?;
 
-        // Wrap structs in parens for convenience
-        let decoded_html_string = match key_type.type_info() {
-            TypeInfo::Struct(_) | TypeInfo::TupleStruct(_) => {
-                Cow::Owned(format!("({})", decoded_html_string))
-            },
-            _ => decoded_html_string
-        };
-
-        let mut ron_de = ron::Deserializer::from_str_with_options(
-            &decoded_html_string, &ron_options
-        ).expect("Couldn't construct RON deserializer");
-
-        let deserialized: Box<dyn Reflect> = DeserializeSeed::deserialize(
-            TypedPartialReflectDeserializer::new(world, key_type, type_registry, default_impl.is_none()),
-            &mut ron_de
-        ).unwrap();
-        Some(deserialized)
-    } else {
-        None
-    };
+    DeserializeSeed::deserialize(
+        TypedPartialReflectDeserializer::new(world, key_type, type_registry, default_impl.is_none()),
+        &mut ron_de
+    ).map_err(|_| deserialization_failed())
+}
+
+fn construct_instance(world: &mut World, type_registry: &TypeRegistry, key_type: &TypeRegistration, value: Option<&str>) -> Result<Box<dyn Reflect>, HTMLSceneSpawnError> {
+    let default_impl = type_registry.get_type_data::<ReflectDefault>(key_type.type_id());
+
+    let instance = value.map(|value| deserialize_instance(world, type_registry, key_type, value)).transpose()?;
 
     let instance: Box<dyn Reflect> = match (instance, default_impl) {
         // Get the default instance... or
@@ -190,125 +249,370 @@ fn construct_instance(world: &mut World, type_registry: &TypeRegistry, key_type:
 struct InterimTextStyle {
     size: f32, color: Color, font: Handle<Font>
 }
-fn spawn_scene(
-    scene: &HTMLScene, replace: Entity, world: &mut World
+fn parse_text_style(
+    commands: &mut EntityWorldMut, type_registry: &TypeRegistry, value: &str
+) -> Result<TextStyle, HTMLSceneSpawnError> {
+    let value = html_escape::decode_html_entities(value);
+    let value = resolve_theme_vars(commands.world().get_resource::<HtmlTheme>(), &value)?;
+    let wrapped_value = format!("({})", value);
+    let mut ron_de = ron::Deserializer::from_str(&wrapped_value)
+        .map_err(|_| HTMLSceneSpawnError::DeserializationFailed("TextStyle".to_string()))?;
+    let mut t = InterimTextStyle::default();
+    t.apply(&*commands.world_scope(|world| {
+        TypedPartialReflectDeserializer::new(world,
+            type_registry.get(std::any::TypeId::of::<InterimTextStyle>()).unwrap(),
+            type_registry,
+            false
+        ).deserialize(&mut ron_de)
+    }).map_err(|_| HTMLSceneSpawnError::DeserializationFailed("TextStyle".to_string()))?);
+    Ok(TextStyle { font_size: t.size, color: t.color, font: t.font })
+}
+
+fn attribute_registration<'a>(
+    type_registry: &'a TypeRegistry, attribute: &str
+) -> Result<&'a TypeRegistration, HTMLSceneSpawnError> {
+    // Lowercase htmx style attributes refer to X components, e.g. x-error-target to XErrorTarget
+    let attribute = match attribute.strip_prefix("x-") {
+        Some(name) => name.split('-')
+            .flat_map(|word| {
+                let mut chars = word.chars();
+                chars.next().into_iter().flat_map(char::to_uppercase).chain(chars)
+            })
+            .fold("X".to_string(), |mut name, c| { name.push(c); name }),
+        None => match attribute {
+            // HTML attributes with component equivalents
+            "tabindex" => "TabIndex".to_string(),
+            "autofocus" => "AutoFocus".to_string(),
+            "disabled" => "Disabled".to_string(),
+            _ => attribute.to_string()
+        }
+    };
+    let attribute = attribute.as_str();
+    // Allow for generic types
+    let attribute = if let Some((attribute, attribute_type)) = attribute.split_once(":") {
+        format!("{attribute}<{attribute_type}>")
+    } else {
+        attribute.to_string()
+    };
+
+    type_registry
+        .get_with_short_type_path(&attribute)
+        .ok_or(HTMLSceneSpawnError::UndefinedComponent(attribute))
+}
+
+fn insert_attribute(
+    commands: &mut EntityWorldMut, type_registry: &TypeRegistry, attribute_reg: &TypeRegistration, value: Option<&str>
 ) -> Result<(), HTMLSceneSpawnError> {
-    fn helper(
-        html_el: &html_parser::Element, commands: &mut EntityWorldMut
-    ) -> Result<(), HTMLSceneSpawnError> {
-        let mut text_style = TextStyle::default();
-
-        // If there's a registered template function
-        // if let Some(template) = commands.world_scope(|world| {
-        //     world.resource_scope(|world, named_system_registry: Mut<NamedSystemRegistry>| {
-        //         named_system_registry.call::<(), HTMLScene>(world, &html_el.name, ())
-        //     })
-        // }) {
-        //     // Recurse with the template's XML
-        //     helper(&template.dom().children.first().unwrap().element().unwrap(), commands)?;
-        // } else if html_el.name != "Entity" { // Null tag
-        //     return Err(HTMLSceneSpawnError::UnrecognizedTagName(html_el.name.to_string()));
-        // }
-
-        let mut components: Vec<(&str, Option<&str>)> = std::iter::once((html_el.name.as_str(), None))
-            .chain(html_el.attributes.iter().map(|x| (x.0.as_str(), x.1.as_ref().map(|s| s.as_str())))).collect();
-        if let Some((_, v)) = components.iter().find(|x| x.0 == "x") {
-            components[0] = (&html_el.name, *v);
+    let attribute = attribute_reg.type_info().type_path();
+    let instance = commands.world_scope(|world| {
+        construct_instance(world, type_registry, attribute_reg, value)
+    })?;
+
+    // Insert our component
+    let reflect_component = type_registry
+        .get_with_type_path(instance.get_represented_type_info().unwrap().type_path())
+        .expect(&format!("Attribute name [{attribute}]: Not registered in TypeRegistry"))
+        .data::<ReflectComponent>()
+        .ok_or_else(|| HTMLSceneSpawnError::MissingReflectComponent(attribute.to_string()))?;
+    reflect_component.insert(commands, &*instance);
+    Ok(())
+}
+
+// Marks the node of a template that the element's child elements go in, written as the `x-slot` attribute,
+// e.g. a ScrollView's content. Swaps into the element's children use it too.
+#[derive(Component, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Default)]
+pub struct XSlot;
+
+// The entity an element's child elements are spawned into
+pub(crate) fn children_slot(world: &World, entity: Entity) -> Entity {
+    world.get::<Children>(entity).into_iter().flatten()
+        .find(|child| world.get::<XSlot>(**child).is_some())
+        .copied()
+        .unwrap_or(entity)
+}
+
+// The element an entity was spawned from (without child elements), used to reconcile later swaps
+#[derive(Component, Clone)]
+struct HTMLSource(html_parser::Element);
+impl HTMLSource {
+    fn new(html_el: &html_parser::Element) -> Self {
+        let mut source = html_el.clone();
+        source.children.retain(|child| child.text().is_some());
+        HTMLSource(source)
+    }
+}
+
+fn element_key(html_el: &html_parser::Element) -> Option<&str> {
+    html_el.attributes.get("key").and_then(|key| key.as_deref()).or(html_el.id.as_deref())
+}
+fn element_text(html_el: &html_parser::Element) -> Option<&str> {
+    html_el.children.iter().find_map(|child| child.text())
+}
+
+fn spawn_element(
+    html_el: &html_parser::Element, commands: &mut EntityWorldMut, children: bool
+) -> Result<(), HTMLSceneSpawnError> {
+    let mut text_style = TextStyle::default();
+
+    // If there's a registered template function
+    // if let Some(template) = commands.world_scope(|world| {
+    //     NamedSystemRegistry::call::<(), HTMLScene>(world, &html_el.name, ())
+    // }) {
+    //     // Recurse with the template's XML
+    //     spawn_element(&template.dom().children.first().unwrap().element().unwrap(), commands)?;
+    // } else if html_el.name != "Entity" { // Null tag
+    //     return Err(HTMLSceneSpawnError::UnrecognizedTagName(html_el.name.to_string()));
+    // }
+
+    let mut components: Vec<(&str, Option<&str>)> = std::iter::once((html_el.name.as_str(), None))
+        .chain(html_el.attributes.iter().map(|x| (x.0.as_str(), x.1.as_ref().map(|s| s.as_str())))).collect();
+    if let Some((_, v)) = components.iter().find(|x| x.0 == "x") {
+        components[0] = (&html_el.name, *v);
+    }
+    // Only apply the attributes when patching an existing entity
+    if !children {
+        components.remove(0);
+    }
+
+    for (attribute, value) in components.into_iter() {
+        let type_registry_arc = commands.world().resource::<AppTypeRegistry>().0.clone();
+        let type_registry = type_registry_arc.read();
+
+        match attribute {
+            "Entity" => {continue;}, // Null attribute
+            "x" => {continue;}, // Placeholder attribute to allow assigning to tag
+            "key" => {continue;}, // Identifies the element when reconciling swaps
+            "x-swap-oob" => {continue;}, // Out of band swap marker, handled by swap_system
+            "TextStyle" if value.is_some() => {
+                text_style = parse_text_style(commands, &type_registry, value.unwrap())?;
+                continue;
+            },
+            "x-transition" if value.is_some() => {
+                commands.insert(parse_x_transition(value.unwrap())?);
+                continue;
+            },
+            _ => ()
+        }
+
+        // Applied while the condition holds instead of right away, e.g. BackgroundColor:focus or Style@max-width:800
+        if let Some((component, condition)) = split_style_condition(attribute)? {
+            let attribute_reg = attribute_registration(&type_registry, component)?;
+            insert_conditional_style(commands, &type_registry, attribute, condition, attribute_reg, value)?;
+            continue;
         }
 
-        for (attribute, value) in components.into_iter() {
-            let type_registry_arc = commands.world().resource::<AppTypeRegistry>().0.clone();
-            let type_registry = type_registry_arc.read();
-
-            match attribute {
-                "Entity" => {continue;}, // Null attribute
-                "x" => {continue;}, // Placeholder attribute to allow assigning to tag
-                "TextStyle" if value.is_some() => {
-                    let wrapped_value = format!("({})", html_escape::decode_html_entities(value.unwrap()));
-                    let mut ron_de = ron::Deserializer::from_str(&wrapped_value).unwrap();
-                    let mut t = InterimTextStyle::default();
-                    t.apply(&*commands.world_scope(|world| {
-                        TypedPartialReflectDeserializer::new(world,
-                            type_registry.get(std::any::TypeId::of::<InterimTextStyle>()).unwrap(),
-                            &type_registry,
-                            false
-                        ).deserialize(&mut ron_de).unwrap()
-                    }));
-                    text_style.font_size = t.size;
-                    text_style.color = t.color;
-                    text_style.font = t.font;
-                    continue;
-                },
-                _ => ()
+        let attribute_reg = attribute_registration(&type_registry, attribute)?;
+
+        if attribute == html_el.name {
+            if let Some(template) = type_registry.get_type_data::<ReflectIntoHTMLScene>(attribute_reg.type_id()) {
+                let instance = commands.world_scope(|world| {
+                    construct_instance(world, &type_registry, attribute_reg, value)
+                })?;
+                // Recurse with the template's XML
+                let template = template.into(instance);
+                spawn_element(&template.dom().children.first().unwrap().element().unwrap(), commands, true)?;
             }
+        }
 
-            // Allow for generic types
-            let attribute = if let Some((attribute, attribute_type)) = attribute.split_once(":") {
-                format!("{attribute}<{attribute_type}>")
-            } else {
-                attribute.to_string()
-            };
+        insert_attribute(commands, &type_registry, attribute_reg, value)?;
+    }
+    if let Some(id) = html_el.id.as_ref() {
+        commands.insert(Name::from(id.as_str()));
+    }
 
-            let attribute_reg: &TypeRegistration = type_registry
-                .get_with_short_type_path(&attribute)
-                .expect(&format!("Attribute name [{}]: Referred to undefined component", &attribute));
-
-            let instance = commands.world_scope(|world| {
-                construct_instance(world, &type_registry, attribute_reg, value)
-            })?;
-
-            if &attribute == &html_el.name {
-                if let Some(template) = 
-                    type_registry.get_type_data::<ReflectIntoHTMLScene>(type_registry.get_with_short_type_path(&attribute).unwrap().type_id())
-                {
-                    // Recurse with the template's XML
-                    let template = template.into(instance);
-                    helper(&template.dom().children.first().unwrap().element().unwrap(), commands)?;
-                }
+    if !children {
+        // Keep the source in step with the applied attributes for later morphs
+        if let Some(mut source) = commands.get_mut::<HTMLSource>() {
+            source.0.attributes.extend(html_el.attributes.clone());
+            if html_el.id.is_some() {
+                source.0.id = html_el.id.clone();
             }
+        }
+        return Ok(());
+    }
 
-            let instance = commands.world_scope(|world| {
-                construct_instance(world, &type_registry, attribute_reg, value)
-            })?;
-
-            // Insert our component
-            let reflect_component = type_registry
-                .get_with_type_path(instance.get_represented_type_info().unwrap().type_path())
-                .expect(&format!("Attribute name [{attribute}]: Not registered in TypeRegistry"))
-                .data::<ReflectComponent>()
-                .expect(&format!("Attribute name [{attribute}]: Missing ReflectComponent type data"));
-            reflect_component.insert(commands, &*instance);
+    if let Some(text) = element_text(html_el) {
+        commands.insert(Text::from_section(text, text_style));
+    }
+    
+    let slot = children_slot(commands.world(), commands.id());
+    commands.world_scope(|world| {
+        let mut children = Vec::new();
+        for child in &html_el.children {
+            if let html_parser::Node::Element(child) = child {
+                let mut child_entity = world.spawn_empty();
+                children.push(child_entity.id());
+                spawn_element(&child, &mut child_entity, true)?;
+            }
         }
-        if let Some(id) = html_el.id.as_ref() {
-            commands.insert(Name::from(id.as_str()));
+        world.entity_mut(slot).push_children(children.as_slice());
+        Ok(())
+    })?;
+    commands.insert(HTMLSource::new(html_el));
+    Ok(())
+}
+
+// Re-applies a single attribute of an element to its existing entity
+fn patch_attribute(
+    commands: &mut EntityWorldMut, type_registry: &TypeRegistry, html_el: &html_parser::Element, attribute: &str, value: Option<&str>
+) -> Result<(), HTMLSceneSpawnError> {
+    match attribute {
+        "Entity" | "key" | "x-swap-oob" | "TextStyle" => Ok(()),
+        "x-transition" if value.is_some() => {
+            commands.insert(parse_x_transition(value.unwrap())?);
+            Ok(())
+        },
+        "x" => insert_attribute(commands, type_registry, attribute_registration(type_registry, &html_el.name)?, value),
+        _ => match split_style_condition(attribute)? {
+            Some((component, condition)) => insert_conditional_style(
+                commands, type_registry, attribute, condition, attribute_registration(type_registry, component)?, value
+            ),
+            None => insert_attribute(commands, type_registry, attribute_registration(type_registry, attribute)?, value)
         }
+    }
+}
 
-        for child in &html_el.children {
-            if let Some(text) = child.text() {
-                commands.insert(Text::from_section(text, text_style));
-                break;
+// Removes what an element put on its entity, keeping the entity's place in the hierarchy and scene,
+// so a different element can be spawned onto it
+fn clear_element(commands: &mut EntityWorldMut, type_registry: &TypeRegistry) {
+    let keep = [TypeId::of::<Parent>(), TypeId::of::<Children>(), TypeId::of::<Handle<HTMLScene>>()];
+    let components: Vec<TypeId> = commands.archetype().components()
+        .filter_map(|id| commands.world().components().get_info(id)?.type_id())
+        .filter(|type_id| !keep.contains(type_id))
+        .collect();
+    for component in components {
+        if let Some(reflect_component) = type_registry.get_type_data::<ReflectComponent>(component) {
+            reflect_component.remove(commands);
+        }
+    }
+    commands.remove::<(HTMLSource, ConditionalStyles)>();
+}
+
+// Patches an entity spawned from HTML to match a new element, only touching what changed.
+// Child elements are matched by their `key` (or `id`) attribute and tag, otherwise by position and tag.
+fn morph_element(
+    html_el: &html_parser::Element, commands: &mut EntityWorldMut
+) -> Result<(), HTMLSceneSpawnError> {
+    let type_registry_arc = commands.world().resource::<AppTypeRegistry>().0.clone();
+    let type_registry = type_registry_arc.read();
+
+    let old_el = match commands.get::<HTMLSource>() {
+        Some(HTMLSource(old_el)) if old_el.name == html_el.name => old_el.clone(),
+        _ => {
+            commands.despawn_descendants();
+            if commands.contains::<HTMLSource>() {
+                clear_element(commands, &type_registry);
+            }
+            drop(type_registry);
+            return spawn_element(html_el, commands, true);
+        }
+    };
+
+    // Attributes
+    restore_conditional_styles(commands, &type_registry);
+    for attribute in old_el.attributes.keys().filter(|attribute| !html_el.attributes.contains_key(*attribute)) {
+        match attribute.as_str() {
+            "Entity" | "key" | "x-swap-oob" | "TextStyle" => (),
+            "x" => insert_attribute(commands, &type_registry, attribute_registration(&type_registry, &html_el.name)?, None)?,
+            _ if split_style_condition(attribute)?.is_some() => remove_conditional_style(commands, &type_registry, attribute),
+            _ => {
+                attribute_registration(&type_registry, attribute)?
+                    .data::<ReflectComponent>()
+                    .ok_or_else(|| HTMLSceneSpawnError::MissingReflectComponent(attribute.to_string()))?
+                    .remove(commands);
             }
         }
-        
-        let children = commands.world_scope(|world| {
-            let mut children = Vec::new();
-            for child in &html_el.children {
-                if let html_parser::Node::Element(child) = child {
-                    let mut child_entity = world.spawn_empty();
-                    children.push(child_entity.id());
-                    helper(&child, &mut child_entity)?;
+    }
+    for (attribute, value) in html_el.attributes.iter() {
+        if old_el.attributes.get(attribute) == Some(value) {
+            continue;
+        }
+        patch_attribute(commands, &type_registry, html_el, attribute, value.as_deref())?;
+    }
+    if old_el.id != html_el.id {
+        match html_el.id.as_ref() {
+            Some(id) => { commands.insert(Name::from(id.as_str())); },
+            None => { commands.remove::<Name>(); }
+        }
+    }
+
+    // Text
+    let text_style_attribute = |el: &html_parser::Element| el.attributes.get("TextStyle").cloned().flatten();
+    if element_text(&old_el) != element_text(html_el) || text_style_attribute(&old_el) != text_style_attribute(html_el) {
+        match element_text(html_el) {
+            Some(text) => {
+                let text_style = match text_style_attribute(html_el) {
+                    Some(value) => parse_text_style(commands, &type_registry, &value)?,
+                    None => TextStyle::default()
+                };
+                commands.insert(Text::from_section(text, text_style));
+            },
+            None => { commands.remove::<Text>(); }
+        }
+    }
+
+    // Children
+    let slot = children_slot(commands.world(), commands.id());
+    let old_children: Vec<Entity> = commands.world().get::<Children>(slot).map(|children| children.to_vec()).unwrap_or_default();
+    let (mut unmatched, other_children): (Vec<_>, Vec<_>) = old_children.iter()
+        .map(|child| (*child, commands.world().get::<HTMLSource>(*child).map(|source| source.0.clone())))
+        .partition(|(_, source)| source.is_some());
+
+    commands.world_scope(|world| {
+        let mut children = Vec::new();
+        for child in html_el.children.iter().filter_map(|child| child.element()) {
+            let matched = unmatched.iter().position(|(_, old_child)| {
+                let old_child = old_child.as_ref().unwrap();
+                old_child.name == child.name && element_key(old_child) == element_key(child)
+            });
+            let mut child_entity = match matched {
+                Some(i) => world.entity_mut(unmatched.remove(i).0),
+                None => world.spawn_empty()
+            };
+            children.push(child_entity.id());
+            if matched.is_some() {
+                // Preserved elements are left untouched
+                if !child_entity.contains::<XPreserve>() {
+                    morph_element(child, &mut child_entity)?;
                 }
+            } else {
+                spawn_element(child, &mut child_entity, true)?;
             }
-            Ok(children)
-        })?;
-        commands.push_children(children.as_slice());
+        }
+        for (old_child, _) in unmatched.drain(..) {
+            world.entity_mut(old_child).despawn_recursive();
+        }
+        // Keep any children that weren't spawned from HTML
+        children.extend(other_children.into_iter().map(|(child, _)| child));
+        if children != old_children {
+            world.entity_mut(slot).replace_children(children.as_slice());
+        }
         Ok(())
-    }
+    })?;
+
+    commands.insert(HTMLSource::new(html_el));
+    Ok(())
+}
 
+pub(crate) fn spawn_scene(
+    scene: &HTMLScene, replace: Entity, world: &mut World, children: bool
+) -> Result<(), HTMLSceneSpawnError> {
     let mut child = world.entity_mut(replace);
-    helper(
+    spawn_element(
         &scene.dom().children.first().expect("HTMLScene has no children").element().expect("HTMLScene first child is not an element"),
-        &mut child
+        &mut child,
+        children
+    )
+}
+
+pub(crate) fn morph_scene(
+    scene: &HTMLScene, target: Entity, world: &mut World
+) -> Result<(), HTMLSceneSpawnError> {
+    let mut target = world.entity_mut(target);
+    morph_element(
+        &scene.dom().children.first().expect("HTMLScene has no children").element().expect("HTMLScene first child is not an element"),
+        &mut target
     )
 }
 
@@ -331,7 +635,8 @@ pub(crate) fn spawn_scene_system(
 
             world.entity_mut(entity).insert(HTMLSceneInstance);
 
-            spawn_scene(scene, entity, world).expect("Failed to spawn HTMLScene!");
+            spawn_scene(scene, entity, world, true).expect("Failed to spawn HTMLScene!");
+            restore_preserved(world, entity);
         }
     });
 }
@@ -416,11 +721,17 @@ impl Plugin for HTMLPlugin {
         app
             .add_plugins(NamedSystemRegistryPlugin)
             .add_plugins(XPlugin)
+            .add_plugins(scroll::ScrollPlugin)
+            .add_plugins(focus::FocusPlugin)
+            .add_plugins(ConditionalStylePlugin)
+            .add_plugins(ThemePlugin)
+            .add_plugins(TransitionPlugin)
 
             .init_asset::<HTMLScene>()
             .init_asset_loader::<HTMLSceneAssetLoader>()
 
             .register_type::<InterimTextStyle>()
+            .register_type::<XSlot>()
             .register_type::<(String, String)>()
             .register_type_data::<(String, String), ReflectDeserialize>()
 
@@ -441,4 +752,88 @@ impl Plugin for HTMLPlugin {
 
             .add_systems(PreUpdate, spawn_scene_system);
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Component, Reflect, Default)]
+    #[reflect(Component, Default)]
+    struct List;
+    #[derive(Component, Reflect, Default)]
+    #[reflect(Component, Default)]
+    struct Item;
+    #[derive(Component, Reflect, Default)]
+    #[reflect(Component, Default)]
+    struct Header;
+
+    // Spawns the first scene, then morphs it into the second, returning the root's children before and after
+    fn morph(from: &str, to: &str) -> (World, Vec<Entity>, Vec<Entity>) {
+        let mut world = World::new();
+        let type_registry = AppTypeRegistry::default();
+        {
+            let mut type_registry = type_registry.write();
+            type_registry.register::<List>();
+            type_registry.register::<Item>();
+            type_registry.register::<Header>();
+        }
+        world.insert_resource(type_registry);
+
+        let root = world.spawn_empty().id();
+        spawn_scene(&HTMLScene::try_from(from).unwrap(), root, &mut world, true).unwrap();
+        let before = world.get::<Children>(root).unwrap().to_vec();
+        morph_scene(&HTMLScene::try_from(to).unwrap(), root, &mut world).unwrap();
+        let after = world.get::<Children>(root).unwrap().to_vec();
+        (world, before, after)
+    }
+
+    fn text(world: &World, entity: Entity) -> &str {
+        &world.get::<Text>(entity).unwrap().sections[0].value
+    }
+
+    #[test]
+    fn morph_matches_children_by_key() {
+        let (world, before, after) = morph(
+            r#"<List><Item key="a">A</Item><Item key="b">B</Item></List>"#,
+            r#"<List><Item key="b">B</Item><Item key="a">A</Item></List>"#
+        );
+        assert_eq!(after, [before[1], before[0]]);
+        assert_eq!(text(&world, after[0]), "B");
+    }
+
+    #[test]
+    fn morph_matches_children_by_id() {
+        let (world, before, after) = morph(
+            r#"<List><Item id="a">A</Item><Item id="b">B</Item></List>"#,
+            r#"<List><Item id="c">C</Item><Item id="a">A2</Item></List>"#
+        );
+        assert_eq!(after.len(), 2);
+        assert!(!before.contains(&after[0]));
+        assert_eq!(after[1], before[0]);
+        assert_eq!(text(&world, after[1]), "A2");
+        assert!(world.get_entity(before[1]).is_none());
+    }
+
+    #[test]
+    fn morph_replaces_children_with_another_tag() {
+        let (world, before, after) = morph(
+            r#"<List><Item>A</Item></List>"#,
+            r#"<List><Header>A</Header></List>"#
+        );
+        assert_ne!(after[0], before[0]);
+        assert!(world.get_entity(before[0]).is_none());
+        assert!(world.get::<Header>(after[0]).is_some());
+        assert!(world.get::<Item>(after[0]).is_none());
+    }
+
+    #[test]
+    fn morph_matches_unkeyed_children_by_position() {
+        let (world, before, after) = morph(
+            r#"<List><Item>A</Item><Item>B</Item></List>"#,
+            r#"<List><Item>C</Item><Item>D</Item><Item>E</Item></List>"#
+        );
+        assert_eq!(after[..2], before[..]);
+        assert!(!before.contains(&after[2]));
+        assert_eq!([text(&world, after[0]), text(&world, after[1]), text(&world, after[2])], ["C", "D", "E"]);
+    }
+}
@@ -1,79 +1,212 @@
-use std::{collections::HashMap, any::TypeId};
+use std::any::TypeId;
 
-use bevy::{ecs::{system::{Resource, IntoSystem, System}, world::World, entity::Entity, component::Component}, app::{Plugin, App}, reflect::Reflect};
+use bevy::{ecs::{system::{Resource, IntoSystem, System}, world::World}, app::{Plugin, App}, reflect::{Reflect, TypePath}, utils::HashMap};
+use thiserror::Error;
 
 pub trait NamedSystemRegistryExt {
     fn register_named_system<M, S, In, Out>(&mut self, name: impl AsRef<str>, system: S) -> &mut Self
-    where S: IntoSystem<In, Out, M> + 'static, In: Reflect + 'static, Out: Reflect + 'static;
+    where S: IntoSystem<In, Out, M> + 'static, In: Reflect + TypePath, Out: Reflect + TypePath;
+    // Registers a function with exclusive world access, which unlike exclusive systems can also take an input
+    fn register_exclusive_named_system<F, In, Out>(&mut self, name: impl AsRef<str>, system: F) -> &mut Self
+    where F: FnMut(&mut World, In) -> Out + Send + Sync + 'static, In: Reflect + TypePath, Out: Reflect + TypePath;
 }
 impl NamedSystemRegistryExt for App {
-    fn register_named_system<M, S, In, Out>(&mut self, name: impl AsRef<str>, system: S) -> &mut Self 
-        where S: IntoSystem<In, Out, M> + 'static, In: Reflect + 'static, Out: Reflect + 'static {
-
-        let mut system = IntoSystem::into_system(system);
-        system.initialize(&mut self.world);
-        let entity = self.world.spawn_empty()
-            .insert(NamedSystem::<In, Out>(Some(Box::new(system))))
-            .insert(CallNamedSystem(
-                Some(Box::new(
-                    |world, entity, in_ref| {
-                        // Take system out
-                        let mut sys = world.entity_mut(entity)
-                            .get_mut::<NamedSystem<In, Out>>()
-                            .unwrap().0.take().unwrap();
-                        // Call system with world access
-                        let res_ref = Box::new(sys.run(*in_ref.downcast().unwrap(), world));
-                        // Put system back in
-                        world.entity_mut(entity)
-                            .get_mut::<NamedSystem<In, Out>>()
-                            .unwrap().0 = Some(sys);
-
-                        res_ref
-                    }
-                ))
-            )).id();
-
-        let mut named_system_reg = self.world.resource_mut::<NamedSystemRegistry>();
-        (*named_system_reg).systems.insert(
-            name.as_ref().to_string(),
-            (entity, TypeId::of::<In>(), TypeId::of::<Out>())
-        );
+    fn register_named_system<M, S, In, Out>(&mut self, name: impl AsRef<str>, system: S) -> &mut Self
+        where S: IntoSystem<In, Out, M> + 'static, In: Reflect + TypePath, Out: Reflect + TypePath {
+        NamedSystemRegistry::register(&mut self.world, name, system);
+        self
+    }
 
+    fn register_exclusive_named_system<F, In, Out>(&mut self, name: impl AsRef<str>, system: F) -> &mut Self
+        where F: FnMut(&mut World, In) -> Out + Send + Sync + 'static, In: Reflect + TypePath, Out: Reflect + TypePath {
+        self.world.resource_mut::<NamedSystemRegistry>().register_exclusive(name, system);
         self
     }
 }
 
-#[derive(Component)]
-struct NamedSystem<In, Out>(Option<Box<dyn System<In = In, Out = Out>>>);
-#[derive(Component)]
-struct CallNamedSystem(Option<Box<dyn Fn(&mut World, Entity, Box<dyn Reflect>) -> Box<dyn Reflect> + Send + Sync>>);
+type CallNamedSystem = Box<dyn FnMut(&mut World, Box<dyn Reflect>) -> Box<dyn Reflect> + Send + Sync>;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum NamedSystemError {
+    #[error("Named system [{name}]: Not registered{}", did_you_mean(.suggestions))]
+    NotFound { name: String, suggestions: Vec<String> },
+    #[error("Named system [{name}]: Takes {expected} as input, not {actual}")]
+    WrongInput { name: String, expected: &'static str, actual: String },
+    #[error("Named system [{name}]: Returns {actual}, not {expected}")]
+    WrongOutput { name: String, expected: &'static str, actual: &'static str },
+    #[error("Named system [{name}]: Called while it's already running")]
+    AlreadyRunning { name: String }
+}
+fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        _ => format!(", did you mean {}?", suggestions.iter().map(|s| format!("[{s}]")).collect::<Vec<_>>().join(" or "))
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = diagonal + (a != *b) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+// Identifies one registration of a named system, replacing a system under the same name gives it a new id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NamedSystemId(u64);
+
+struct NamedSystem {
+    name: String,
+    in_type: (TypeId, &'static str),
+    out_type: (TypeId, &'static str),
+    // Taken out of the registry while the system runs
+    call: Option<CallNamedSystem>
+}
+
+#[derive(Debug, Clone)]
+pub struct NamedSystemInfo<'a> {
+    pub id: NamedSystemId,
+    pub name: &'a str,
+    pub in_type_path: &'static str,
+    pub out_type_path: &'static str
+}
 
+// Systems are owned by the registry and called with the registry left in the world,
+// so named systems can call other named systems
 #[derive(Resource, Default)]
 pub struct NamedSystemRegistry {
-    systems: HashMap<String, (Entity, TypeId, TypeId)>
+    names: HashMap<String, NamedSystemId>,
+    systems: HashMap<NamedSystemId, NamedSystem>,
+    next_id: u64
 }
 impl NamedSystemRegistry {
-    pub fn call_reflect(&self, world: &mut World, name: &str, in_ref: Box<dyn Reflect>) -> Option<Box<dyn Reflect>> {
-        let entity = self.systems.get(name)?.0;
+    fn insert<In: TypePath, Out: TypePath>(&mut self, name: impl AsRef<str>, call: CallNamedSystem) -> NamedSystemId {
+        let id = NamedSystemId(self.next_id);
+        self.next_id += 1;
 
-        // Take function out
-        let f = world.entity_mut(entity).get_mut::<CallNamedSystem>()?.0.take().unwrap();
-
-        let res_ref = f(world, entity, in_ref);
+        // Registering under an existing name replaces the previous system
+        if let Some(old) = self.names.insert(name.as_ref().to_string(), id) {
+            self.systems.remove(&old);
+        }
+        self.systems.insert(id, NamedSystem {
+            name: name.as_ref().to_string(),
+            in_type: (TypeId::of::<In>(), In::type_path()),
+            out_type: (TypeId::of::<Out>(), Out::type_path()),
+            call: Some(call)
+        });
+        id
+    }
 
-        // Put function back in
-        world.entity_mut(entity).get_mut::<CallNamedSystem>()?.0 = Some(f);
+    // Registers (or replaces) a system, initializing it with the world
+    pub fn register<M, S, In, Out>(world: &mut World, name: impl AsRef<str>, system: S) -> NamedSystemId
+        where S: IntoSystem<In, Out, M> + 'static, In: Reflect + TypePath, Out: Reflect + TypePath {
+        let mut system = IntoSystem::into_system(system);
+        system.initialize(world);
+        world.resource_mut::<Self>().insert::<In, Out>(name, Box::new(move |world, in_ref| {
+            // Call system with world access, then apply its commands
+            let res_ref = Box::new(system.run(*in_ref.downcast().unwrap(), world));
+            system.apply_deferred(world);
+            res_ref
+        }))
+    }
+    pub fn register_exclusive<F, In, Out>(&mut self, name: impl AsRef<str>, mut system: F) -> NamedSystemId
+        where F: FnMut(&mut World, In) -> Out + Send + Sync + 'static, In: Reflect + TypePath, Out: Reflect + TypePath {
+        self.insert::<In, Out>(name, Box::new(move |world, in_ref| {
+            Box::new(system(world, *in_ref.downcast().unwrap()))
+        }))
+    }
+    pub fn replace<M, S, In, Out>(world: &mut World, name: impl AsRef<str>, system: S) -> Option<NamedSystemId>
+        where S: IntoSystem<In, Out, M> + 'static, In: Reflect + TypePath, Out: Reflect + TypePath {
+        if !world.resource::<Self>().contains(name.as_ref()) {
+            return None;
+        }
+        Some(Self::register(world, name, system))
+    }
+    pub fn unregister(&mut self, name: &str) -> Option<NamedSystemId> {
+        let id = self.names.remove(name)?;
+        self.systems.remove(&id);
+        Some(id)
+    }
 
-        Some(res_ref)
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains_key(name)
     }
-    pub fn call<In: Reflect, Out: Reflect>(&self, world: &mut World, name: &str, in_val: In) -> Option<Out> {
-        let in_ref: Box<dyn Reflect> = Box::new(in_val);
-        let res_ref = self.call_reflect(world, name, in_ref)?;
-        res_ref.downcast().ok().map(|x| *x)
+    pub fn id(&self, name: &str) -> Option<NamedSystemId> {
+        self.names.get(name).copied()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = NamedSystemInfo> {
+        self.systems.iter().map(|(id, system)| NamedSystemInfo {
+            id: *id,
+            name: &system.name,
+            in_type_path: system.in_type.1,
+            out_type_path: system.out_type.1
+        })
     }
     pub fn get_type_ids(&self, name: &str) -> Option<(TypeId, TypeId)> {
-        let x = self.systems.get(name)?;
-        Some((x.1, x.2))
+        let system = self.systems.get(self.names.get(name)?)?;
+        Some((system.in_type.0, system.out_type.0))
+    }
+    pub fn get_type_paths(&self, name: &str) -> Option<(&'static str, &'static str)> {
+        let system = self.systems.get(self.names.get(name)?)?;
+        Some((system.in_type.1, system.out_type.1))
+    }
+    // Registered names close to `name`, closest first
+    pub fn similar_names(&self, name: &str) -> Vec<String> {
+        let mut similar: Vec<(usize, &String)> = self.names.keys()
+            .map(|other| (edit_distance(&name.to_lowercase(), &other.to_lowercase()), other))
+            .filter(|(distance, _)| *distance <= (name.len() / 3).max(2))
+            .collect();
+        similar.sort();
+        similar.into_iter().take(3).map(|(_, other)| other.clone()).collect()
+    }
+    fn get(&self, name: &str) -> Result<(NamedSystemId, &NamedSystem), NamedSystemError> {
+        self.names.get(name)
+            .and_then(|id| Some((*id, self.systems.get(id)?)))
+            .ok_or_else(|| NamedSystemError::NotFound { name: name.to_string(), suggestions: self.similar_names(name) })
+    }
+
+    pub fn call_reflect(world: &mut World, name: &str, in_ref: Box<dyn Reflect>) -> Result<Box<dyn Reflect>, NamedSystemError> {
+        let mut registry = world.resource_mut::<Self>();
+        let (id, system) = registry.get(name)?;
+        if in_ref.as_any().type_id() != system.in_type.0 {
+            return Err(NamedSystemError::WrongInput {
+                name: name.to_string(),
+                expected: system.in_type.1,
+                actual: in_ref.reflect_type_path().to_string()
+            });
+        }
+
+        // Take the system out while it runs
+        let Some(mut call) = registry.systems.get_mut(&id).unwrap().call.take() else {
+            return Err(NamedSystemError::AlreadyRunning { name: name.to_string() });
+        };
+
+        let res_ref = call(world, in_ref);
+
+        // Put it back in, unless it was unregistered or replaced in the meantime
+        if let Some(system) = world.resource_mut::<Self>().systems.get_mut(&id) {
+            system.call = Some(call);
+        }
+
+        Ok(res_ref)
+    }
+    pub fn call<In: Reflect, Out: Reflect + TypePath>(world: &mut World, name: &str, in_val: In) -> Result<Out, NamedSystemError> {
+        let (_, system) = world.resource::<Self>().get(name)?;
+        if TypeId::of::<Out>() != system.out_type.0 {
+            return Err(NamedSystemError::WrongOutput { name: name.to_string(), expected: Out::type_path(), actual: system.out_type.1 });
+        }
+
+        let in_ref: Box<dyn Reflect> = Box::new(in_val);
+        let res_ref = Self::call_reflect(world, name, in_ref)?;
+        Ok(*res_ref.downcast().unwrap())
     }
 }
 
@@ -82,4 +215,89 @@ impl Plugin for NamedSystemRegistryPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<NamedSystemRegistry>();
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::{In, ResMut};
+
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct Calls(u32);
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.init_resource::<NamedSystemRegistry>();
+        world.init_resource::<Calls>();
+        NamedSystemRegistry::register(&mut world, "increment", |input: In<u32>, mut calls: ResMut<Calls>| {
+            calls.0 += 1;
+            input.0 + 1
+        });
+        NamedSystemRegistry::register(&mut world, "decrement", |input: In<u32>| input.0 - 1);
+        NamedSystemRegistry::register(&mut world, "reset", || 0u32);
+        world
+    }
+
+    #[test]
+    fn calls_by_name() {
+        let mut world = world();
+        assert_eq!(NamedSystemRegistry::call::<u32, u32>(&mut world, "increment", 1), Ok(2));
+        assert_eq!(world.resource::<Calls>().0, 1);
+    }
+
+    #[test]
+    fn not_found_suggests_similar_names() {
+        let mut world = world();
+        let error = NamedSystemRegistry::call::<u32, u32>(&mut world, "incremnet", 1).unwrap_err();
+        assert_eq!(error, NamedSystemError::NotFound { name: "incremnet".to_string(), suggestions: vec!["increment".to_string()] });
+        assert_eq!(error.to_string(), "Named system [incremnet]: Not registered, did you mean [increment]?");
+
+        let error = NamedSystemRegistry::call::<u32, u32>(&mut world, "save", 1).unwrap_err();
+        assert_eq!(error.to_string(), "Named system [save]: Not registered");
+    }
+
+    #[test]
+    fn wrong_input_is_rejected_before_running() {
+        let mut world = world();
+        let error = NamedSystemRegistry::call::<String, u32>(&mut world, "increment", "1".to_string()).unwrap_err();
+        assert!(matches!(error, NamedSystemError::WrongInput { expected: "u32", .. }));
+        assert_eq!(world.resource::<Calls>().0, 0);
+    }
+
+    #[test]
+    fn wrong_output_is_rejected_before_running() {
+        let mut world = world();
+        let error = NamedSystemRegistry::call::<u32, String>(&mut world, "increment", 1).unwrap_err();
+        assert_eq!(error, NamedSystemError::WrongOutput { name: "increment".to_string(), expected: "alloc::string::String", actual: "u32" });
+        assert_eq!(world.resource::<Calls>().0, 0);
+    }
+
+    #[test]
+    fn reentrant_call_is_already_running() {
+        let mut world = world();
+        world.resource_mut::<NamedSystemRegistry>().register_exclusive("recurse", |world: &mut World, _: ()| {
+            matches!(NamedSystemRegistry::call::<(), bool>(world, "recurse", ()), Err(NamedSystemError::AlreadyRunning { .. }))
+        });
+        assert_eq!(NamedSystemRegistry::call::<(), bool>(&mut world, "recurse", ()), Ok(true));
+        // The system is put back afterwards
+        assert_eq!(NamedSystemRegistry::call::<(), bool>(&mut world, "recurse", ()), Ok(true));
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("same", "same"), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("flaw", "lawn"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn similar_names_are_closest_first() {
+        let world = world();
+        let registry = world.resource::<NamedSystemRegistry>();
+        assert_eq!(registry.similar_names("Reset"), vec!["reset"]);
+        assert_eq!(registry.similar_names("crement"), vec!["decrement", "increment"]);
+        assert!(registry.similar_names("save").is_empty());
+    }
+}
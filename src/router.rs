@@ -0,0 +1,245 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Serialize, Deserialize};
+
+use crate::HTMLScene;
+use crate::htmx::{HTMLResponse, HtmlRequest, XFunction, XOn, XSwap, run_x_function};
+
+#[derive(Debug, Clone)]
+pub enum HtmlRoute {
+    System(String), // Named system rendering the route, returning anything an XFunction can
+    Scene(String) // Path of an HTMLScene asset
+}
+
+// Navigation stack rendered into every XOutlet element
+#[derive(Resource, Debug)]
+pub struct HtmlRouter {
+    routes: HashMap<String, HtmlRoute>,
+    history: Vec<String>,
+    current: usize,
+    dirty: bool,
+    pub back_button: Option<GamepadButtonType> // Gamepad button that goes back, if it isn't handled by an XRoute trigger
+}
+impl Default for HtmlRouter {
+    fn default() -> Self {
+        Self {
+            routes: HashMap::default(),
+            history: Vec::new(),
+            current: 0,
+            dirty: false,
+            back_button: None
+        }
+    }
+}
+impl HtmlRouter {
+    pub fn with_route(mut self, path: impl Into<String>, route: HtmlRoute) -> Self {
+        self.add_route(path, route);
+        self
+    }
+    pub fn add_route(&mut self, path: impl Into<String>, route: HtmlRoute) -> &mut Self {
+        self.routes.insert(path.into(), route);
+        self
+    }
+
+    pub fn current(&self) -> Option<&str> {
+        self.history.get(self.current).map(|path| path.as_str())
+    }
+    pub fn can_go_back(&self) -> bool {
+        self.current > 0
+    }
+    pub fn can_go_forward(&self) -> bool {
+        self.current + 1 < self.history.len()
+    }
+
+    pub fn push(&mut self, path: impl Into<String>) {
+        // Pushing discards any forward history
+        if !self.history.is_empty() {
+            self.history.truncate(self.current + 1);
+        }
+        self.history.push(path.into());
+        self.current = self.history.len() - 1;
+        self.dirty = true;
+    }
+    pub fn replace(&mut self, path: impl Into<String>) {
+        match self.history.get_mut(self.current) {
+            Some(current) => { *current = path.into(); self.dirty = true; },
+            None => self.push(path)
+        }
+    }
+    pub fn back(&mut self) -> bool {
+        if !self.can_go_back() {
+            return false;
+        }
+        self.current -= 1;
+        self.dirty = true;
+        true
+    }
+    pub fn forward(&mut self) -> bool {
+        if !self.can_go_forward() {
+            return false;
+        }
+        self.current += 1;
+        self.dirty = true;
+        true
+    }
+
+    pub fn navigate(&mut self, route: &XRoute) {
+        match route {
+            XRoute::Push(path) => self.push(path.as_str()),
+            XRoute::Replace(path) => self.replace(path.as_str()),
+            XRoute::Back => { self.back(); },
+            XRoute::Forward => { self.forward(); }
+        }
+    }
+}
+
+// Navigates the HtmlRouter when triggered
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize)]
+pub enum XRoute {
+    Push(String),
+    Replace(String),
+    #[default]
+    Back,
+    Forward
+}
+#[derive(Component, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Default)]
+pub struct XOutlet;
+
+pub(crate) fn router_back_button(
+    mut router: ResMut<HtmlRouter>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>
+) {
+    let Some(back_button) = router.back_button else { return; };
+    if gamepads.iter().any(|gamepad| gamepad_buttons.just_pressed(GamepadButton::new(gamepad, back_button))) {
+        router.back();
+    }
+}
+
+// Each outlet is the trigger and target of its route's named system, so its output, errors and tasks
+// are handled like an XFunction's
+pub(crate) fn render_routes(world: &mut World) -> Vec<(Entity, HTMLResponse)> {
+    let mut outlets = world.query_filtered::<Entity, With<XOutlet>>();
+    let mut new_outlets = world.query_filtered::<Entity, Added<XOutlet>>();
+
+    let outlets: Vec<Entity> = if world.resource::<HtmlRouter>().dirty {
+        world.resource_mut::<HtmlRouter>().dirty = false;
+        outlets.iter(world).collect()
+    } else {
+        new_outlets.iter(world).collect()
+    };
+    if outlets.is_empty() {
+        return Vec::new();
+    }
+
+    let router = world.resource::<HtmlRouter>();
+    let Some(path) = router.current() else { return Vec::new(); };
+    let Some(route) = router.routes.get(path).cloned() else {
+        warn!("No route registered for [{path}]");
+        return Vec::new();
+    };
+
+    let mut responses = Vec::new();
+    match route {
+        HtmlRoute::System(name) => for outlet in outlets {
+            let request = HtmlRequest { trigger: outlet, target: outlet, on: XOn::default() };
+            responses.extend(run_x_function(world, &XFunction(name.clone(), None), request, XSwap::Inner, None)
+                .map(|response| (outlet, response)));
+        },
+        HtmlRoute::Scene(path) => {
+            let handle: Handle<HTMLScene> = world.resource::<AssetServer>().load(path);
+            for outlet in outlets {
+                let child = world.spawn(handle.clone()).id();
+                world.entity_mut(outlet)
+                    .despawn_descendants()
+                    .add_child(child);
+            }
+        }
+    }
+    responses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn router(paths: &[&str]) -> HtmlRouter {
+        let mut router = HtmlRouter::default();
+        for path in paths {
+            router.push(*path);
+        }
+        router
+    }
+
+    #[test]
+    fn push_moves_to_the_new_path() {
+        let router = router(&["main", "settings"]);
+        assert_eq!(router.current(), Some("settings"));
+        assert!(router.can_go_back());
+        assert!(!router.can_go_forward());
+    }
+
+    #[test]
+    fn back_and_forward_walk_the_history() {
+        let mut router = router(&["main", "settings", "audio"]);
+        assert!(router.back());
+        assert!(router.back());
+        assert_eq!(router.current(), Some("main"));
+        assert!(!router.back());
+        assert_eq!(router.current(), Some("main"));
+
+        assert!(router.forward());
+        assert_eq!(router.current(), Some("settings"));
+        assert!(router.forward());
+        assert!(!router.forward());
+        assert_eq!(router.current(), Some("audio"));
+    }
+
+    #[test]
+    fn push_discards_forward_history() {
+        let mut router = router(&["main", "settings", "audio"]);
+        router.back();
+        router.back();
+        router.push("credits");
+        assert_eq!(router.current(), Some("credits"));
+        assert!(!router.can_go_forward());
+        assert!(router.back());
+        assert_eq!(router.current(), Some("main"));
+    }
+
+    #[test]
+    fn replace_swaps_the_current_path() {
+        let mut router = router(&["main", "settings"]);
+        router.replace("audio");
+        assert_eq!(router.current(), Some("audio"));
+        router.back();
+        assert_eq!(router.current(), Some("main"));
+        assert!(router.forward());
+        assert_eq!(router.current(), Some("audio"));
+    }
+
+    #[test]
+    fn replace_on_empty_history_pushes() {
+        let mut router = HtmlRouter::default();
+        assert_eq!(router.current(), None);
+        router.replace("main");
+        assert_eq!(router.current(), Some("main"));
+        assert!(!router.can_go_back());
+    }
+
+    #[test]
+    fn navigation_marks_the_router_dirty() {
+        let mut router = router(&["main"]);
+        router.dirty = false;
+        assert!(!router.back());
+        assert!(!router.dirty);
+        router.navigate(&XRoute::Push("settings".to_string()));
+        assert!(router.dirty);
+        router.dirty = false;
+        router.navigate(&XRoute::Back);
+        assert!(router.dirty);
+        assert_eq!(router.current(), Some("main"));
+    }
+}
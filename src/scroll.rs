@@ -0,0 +1,203 @@
+use bevy::prelude::*;
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::window::PrimaryWindow;
+use maud::html;
+use serde::{Serialize, Deserialize};
+
+use crate::{HTMLScene, ReflectIntoHTMLScene};
+
+// Clipping container whose children are laid out in a ScrollContent node offset by its ScrollOffset,
+// scrolled with the mouse wheel, by dragging, or with the right stick while under the cursor
+#[derive(Component, Serialize, Deserialize, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize, Default)]
+#[serde(default)]
+pub struct ScrollView {
+    pub line_height: f32, // Pixels scrolled per mouse wheel line
+    pub stick_speed: f32 // Pixels scrolled per second with the stick fully tilted
+}
+impl Default for ScrollView {
+    fn default() -> Self {
+        Self { line_height: 20., stick_speed: 800. }
+    }
+}
+impl Into<HTMLScene> for ScrollView {
+    fn into(self) -> HTMLScene {
+        html! {
+            Node ScrollOffset {
+                Node ScrollContent x-slot Style="flex_shrink: 0.0, align_self: FlexStart, min_width: Percent(100.0), min_height: Percent(100.0)" { }
+            }
+        }.into()
+    }
+}
+
+// The node inside a ScrollView that holds its children, laid out with the view's flex container properties
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize, Default)]
+pub struct ScrollContent;
+
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize, Default)]
+pub struct ScrollOffset(pub Vec2);
+
+// Scrolls the element's ScrollView ancestors to show it once it's laid out, e.g. after being swapped in
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Deserialize, Default)]
+pub struct XScrollIntoView;
+#[derive(Component)]
+struct PendingScrollIntoView;
+
+// Scroll view being dragged, with the last cursor position
+#[derive(Default)]
+struct ScrollDrag(Option<(Entity, Vec2)>);
+
+// Innermost scroll view under the cursor
+fn hovered_view(
+    cursor: Option<Vec2>,
+    views: &Query<(Entity, &Node, &GlobalTransform), With<ScrollView>>
+) -> Option<Entity> {
+    let cursor = cursor?;
+    views.iter()
+        .map(|(entity, node, transform)| (entity, node.logical_rect(transform)))
+        .filter(|(_, rect)| rect.contains(cursor))
+        .min_by(|(_, a), (_, b)| a.size().length_squared().total_cmp(&b.size().length_squared()))
+        .map(|(entity, _)| entity)
+}
+
+fn scroll_input(
+    time: Res<Time>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut wheel: EventReader<MouseWheel>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut drag: Local<ScrollDrag>,
+    views: Query<(Entity, &Node, &GlobalTransform), With<ScrollView>>,
+    mut offsets: Query<(&ScrollView, &mut ScrollOffset)>
+) {
+    let cursor = windows.get_single().ok().and_then(|window| window.cursor_position());
+    let hovered = hovered_view(cursor, &views);
+
+    let mut deltas = Vec::new();
+
+    for event in wheel.read() {
+        let Some(entity) = hovered else { continue; };
+        let line_height = offsets.get(entity).map(|(view, _)| view.line_height).unwrap_or(1.);
+        let delta = match event.unit {
+            MouseScrollUnit::Line => Vec2::new(event.x, event.y) * line_height,
+            MouseScrollUnit::Pixel => Vec2::new(event.x, event.y)
+        };
+        deltas.push((entity, -delta));
+    }
+
+    if mouse_buttons.just_pressed(MouseButton::Left) {
+        drag.0 = hovered.zip(cursor);
+    }
+    if !mouse_buttons.pressed(MouseButton::Left) {
+        drag.0 = None;
+    }
+    if let (Some((entity, last)), Some(cursor)) = (drag.0, cursor) {
+        deltas.push((entity, last - cursor));
+        drag.0 = Some((entity, cursor));
+    }
+
+    if let Some(entity) = hovered {
+        let stick_speed = offsets.get(entity).map(|(view, _)| view.stick_speed).unwrap_or(0.);
+        for gamepad in gamepads.iter() {
+            let stick = Vec2::new(
+                axes.get(GamepadAxis::new(gamepad, GamepadAxisType::RightStickX)).unwrap_or(0.),
+                // Stick up scrolls up
+                -axes.get(GamepadAxis::new(gamepad, GamepadAxisType::RightStickY)).unwrap_or(0.)
+            );
+            deltas.push((entity, stick * stick_speed * time.delta_seconds()));
+        }
+    }
+
+    for (entity, delta) in deltas {
+        if let Ok((_, mut offset)) = offsets.get_mut(entity) {
+            offset.0 += delta;
+        }
+    }
+}
+
+fn scroll_into_view(
+    added: Query<Entity, Added<XScrollIntoView>>,
+    pending: Query<(Entity, &Node, &GlobalTransform), With<PendingScrollIntoView>>,
+    parents: Query<&Parent>,
+    mut views: Query<(&Node, &GlobalTransform, &mut ScrollOffset), With<ScrollView>>,
+    mut commands: Commands
+) {
+    for entity in &added {
+        commands.entity(entity).insert(PendingScrollIntoView);
+    }
+
+    for (entity, node, transform) in &pending {
+        // Wait for the layout
+        if node.size() == Vec2::ZERO {
+            continue;
+        }
+        commands.entity(entity).remove::<PendingScrollIntoView>();
+
+        let mut rect = node.logical_rect(transform);
+        for ancestor in parents.iter_ancestors(entity) {
+            let Ok((view_node, view_transform, mut offset)) = views.get_mut(ancestor) else { continue; };
+            let view = view_node.logical_rect(view_transform);
+            let delta = Vec2::new(
+                (rect.max.x - view.max.x).max(0.) + (rect.min.x - view.min.x).min(0.),
+                (rect.max.y - view.max.y).max(0.) + (rect.min.y - view.min.y).min(0.)
+            );
+            offset.0 += delta;
+            // Outer views have to show where the element ends up
+            rect.min -= delta;
+            rect.max -= delta;
+        }
+    }
+}
+
+// Clamps offsets to the content, and moves each scroll view's content node by its offset
+fn apply_scroll(
+    mut views: Query<(&Node, &mut Style, &mut ScrollOffset, &Children), With<ScrollView>>,
+    mut contents: Query<(&Node, &mut Style), (With<ScrollContent>, Without<ScrollView>)>
+) {
+    for (node, mut style, mut offset, children) in &mut views {
+        if style.overflow != Overflow::clip() {
+            style.overflow = Overflow::clip();
+        }
+        let Some(content) = children.iter().find(|child| contents.contains(**child)) else { continue; };
+        let (content_node, mut content_style) = contents.get_mut(*content).unwrap();
+
+        let clamped = offset.0.clamp(Vec2::ZERO, (content_node.size() - node.size()).max(Vec2::ZERO));
+        if clamped != offset.0 {
+            offset.0 = clamped;
+        }
+
+        let (left, top) = (Val::Px(-offset.0.x), Val::Px(-offset.0.y));
+        let layout = |style: &Style| (
+            style.flex_direction, style.flex_wrap, style.justify_content, style.align_items, style.align_content, style.row_gap, style.column_gap
+        );
+        if content_style.left != left || content_style.top != top || layout(&content_style) != layout(&style) {
+            content_style.left = left;
+            content_style.top = top;
+            content_style.flex_direction = style.flex_direction;
+            content_style.flex_wrap = style.flex_wrap;
+            content_style.justify_content = style.justify_content;
+            content_style.align_items = style.align_items;
+            content_style.align_content = style.align_content;
+            content_style.row_gap = style.row_gap;
+            content_style.column_gap = style.column_gap;
+        }
+    }
+}
+
+pub struct ScrollPlugin;
+impl Plugin for ScrollPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .register_type::<ScrollView>()
+            .register_type::<ScrollOffset>()
+            .register_type::<ScrollContent>()
+            .register_type::<XScrollIntoView>()
+            .register_type_data::<ScrollView, ReflectIntoHTMLScene>()
+
+            .add_systems(Update, (scroll_input, scroll_into_view, apply_scroll).chain());
+    }
+}
@@ -0,0 +1,215 @@
+use std::borrow::Cow;
+
+use bevy::prelude::*;
+use bevy::asset::{AssetLoader, AsyncReadExt};
+use bevy::utils::HashMap;
+
+use crate::{HTMLSceneSpawnError, HTMLSource, element_text, parse_text_style, patch_attribute, restore_conditional_styles, spawn_scene_system};
+
+// Named values that attributes can refer to with var(--name), e.g. BackgroundColor="var(--accent)".
+// Values are RON, inserted as is, or without their quotes when referred to inside a string.
+// Changing the resource re-applies the attributes that refer to it.
+#[derive(Resource, Asset, Reflect, Default, Debug, Clone)]
+pub struct HtmlTheme {
+    pub vars: HashMap<String, String>
+}
+impl HtmlTheme {
+    pub fn with(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.vars.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(|value| value.as_str())
+    }
+
+    // Parses variables written like CSS custom properties, e.g. `--accent: "#A77"; --gap: Px(10.0);`
+    pub fn parse(source: &str) -> Self {
+        let mut theme = Self::default();
+        for declaration in source.split(';').map(str::trim).filter(|declaration| !declaration.is_empty()) {
+            match declaration.split_once(':') {
+                Some((name, value)) => {
+                    theme.vars.insert(name.trim().trim_start_matches("--").to_string(), value.trim().to_string());
+                },
+                None => warn!("Theme: Ignoring declaration without a value [{declaration}]")
+            }
+        }
+        theme
+    }
+}
+
+// The asset HtmlTheme is loaded from, reloaded when the asset changes
+#[derive(Resource, Debug, Clone)]
+pub struct HtmlThemeHandle(pub Handle<HtmlTheme>);
+
+#[derive(Default)]
+pub struct HtmlThemeAssetLoader;
+impl AssetLoader for HtmlThemeAssetLoader {
+    type Asset = HtmlTheme;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    fn load<'a>(
+            &'a self,
+            reader: &'a mut bevy::asset::io::Reader,
+            _settings: &'a Self::Settings,
+            _load_context: &'a mut bevy::asset::LoadContext,
+        ) -> bevy::utils::BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut source = String::new();
+            reader.read_to_string(&mut source).await?;
+            Ok(HtmlTheme::parse(&source))
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["theme"]
+    }
+}
+
+pub(crate) fn uses_theme_vars(value: &str) -> bool {
+    value.contains("var(--")
+}
+
+// Replaces var(--name) references in an attribute value with the theme's values
+pub(crate) fn resolve_theme_vars<'a>(theme: Option<&HtmlTheme>, value: &'a str) -> Result<Cow<'a, str>, HTMLSceneSpawnError> {
+    if !uses_theme_vars(value) {
+        return Ok(Cow::Borrowed(value));
+    }
+
+    let mut resolved = String::with_capacity(value.len());
+    let mut in_string = false;
+    let mut rest = value;
+    while let Some(c) = rest.chars().next() {
+        if let Some(reference) = rest.strip_prefix("var(--") {
+            let end = reference.find(')').unwrap_or(reference.len());
+            let name = &reference[..end];
+            let var = theme.and_then(|theme| theme.get(name))
+                .ok_or_else(|| HTMLSceneSpawnError::UndefinedThemeVariable(name.to_string()))?;
+            match in_string {
+                true => resolved.push_str(var.strip_prefix('"').and_then(|var| var.strip_suffix('"')).unwrap_or(var)),
+                false => resolved.push_str(var)
+            }
+            rest = reference.get(end + 1..).unwrap_or_default();
+            continue;
+        }
+
+        let mut len = c.len_utf8();
+        match c {
+            '"' => in_string = !in_string,
+            // Keep escaped characters, including quotes, as they are
+            '\\' if in_string => len += rest[len..].chars().next().map_or(0, char::len_utf8),
+            _ => ()
+        }
+        resolved.push_str(&rest[..len]);
+        rest = &rest[len..];
+    }
+    Ok(Cow::Owned(resolved))
+}
+
+fn load_theme(
+    mut events: EventReader<AssetEvent<HtmlTheme>>,
+    handle: Option<Res<HtmlThemeHandle>>,
+    themes: Res<Assets<HtmlTheme>>,
+    mut theme: ResMut<HtmlTheme>
+) {
+    let Some(handle) = handle else {
+        events.clear();
+        return;
+    };
+    let changed = events.read()
+        .any(|event| event.is_loaded_with_dependencies(&handle.0) || event.is_modified(&handle.0));
+    // Also pick up a handle to an asset that's already loaded
+    if changed || handle.is_changed() {
+        if let Some(loaded) = themes.get(&handle.0) {
+            *theme = loaded.clone();
+        }
+    }
+}
+
+// Re-applies attributes referring to theme variables on entities spawned from HTML
+fn reapply_theme(world: &mut World) {
+    let mut sources = world.query::<(Entity, &HTMLSource)>();
+    let themed: Vec<(Entity, html_parser::Element)> = sources.iter(world)
+        .filter(|(_, source)| source.0.attributes.values().flatten().any(|value| uses_theme_vars(value)))
+        .map(|(entity, source)| (entity, source.0.clone()))
+        .collect();
+
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+    for (entity, html_el) in themed {
+        let mut entity = world.entity_mut(entity);
+        restore_conditional_styles(&mut entity, &type_registry);
+        for (attribute, value) in html_el.attributes.iter() {
+            let Some(value) = value.as_deref().filter(|value| uses_theme_vars(value)) else { continue; };
+            if attribute == "TextStyle" {
+                if let Some(text) = element_text(&html_el) {
+                    match parse_text_style(&mut entity, &type_registry, value) {
+                        Ok(text_style) => { entity.insert(Text::from_section(text, text_style)); },
+                        Err(error) => error!("{error}")
+                    }
+                }
+                continue;
+            }
+            if let Err(error) = patch_attribute(&mut entity, &type_registry, &html_el, attribute, Some(value)) {
+                error!("{error}");
+            }
+        }
+    }
+}
+
+pub struct ThemePlugin;
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_asset::<HtmlTheme>()
+            .init_asset_loader::<HtmlThemeAssetLoader>()
+            .init_resource::<HtmlTheme>()
+
+            .add_systems(PreUpdate, (
+                load_theme,
+                reapply_theme.run_if(resource_changed::<HtmlTheme>())
+            ).chain().before(spawn_scene_system));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn theme() -> HtmlTheme {
+        HtmlTheme::parse(r##"--accent: "#A77"; --gap: Px(10.0); --broken"##)
+    }
+
+    #[test]
+    fn parse_reads_custom_properties() {
+        let theme = theme();
+        assert_eq!(theme.get("accent"), Some(r##""#A77""##));
+        assert_eq!(theme.get("gap"), Some("Px(10.0)"));
+        assert_eq!(theme.vars.len(), 2);
+    }
+
+    #[test]
+    fn defined_vars_are_inserted() {
+        assert_eq!(resolve_theme_vars(Some(&theme()), "var(--accent)").unwrap(), r##""#A77""##);
+        assert_eq!(resolve_theme_vars(None, "Px(10.0)").unwrap(), "Px(10.0)");
+    }
+
+    #[test]
+    fn nested_vars_are_inserted() {
+        assert_eq!(
+            resolve_theme_vars(Some(&theme()), "width: var(--gap), margin: All(var(--gap))").unwrap(),
+            "width: Px(10.0), margin: All(Px(10.0))"
+        );
+        // Without the value's quotes inside a string
+        assert_eq!(resolve_theme_vars(Some(&theme()), r##""4px solid var(--accent)""##).unwrap(), r##""4px solid #A77""##);
+    }
+
+    #[test]
+    fn undefined_vars_are_errors() {
+        let error = resolve_theme_vars(Some(&theme()), "width: var(--gap), height: var(--missing)").unwrap_err();
+        assert!(matches!(&error, HTMLSceneSpawnError::UndefinedThemeVariable(name) if name == "missing"));
+        assert_eq!(error.to_string(), "Theme variable [--missing]: Not defined in HtmlTheme");
+        assert!(resolve_theme_vars(None, "var(--accent)").is_err());
+    }
+}
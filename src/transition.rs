@@ -0,0 +1,365 @@
+use std::any::TypeId;
+use std::str::FromStr;
+
+use bevy::prelude::*;
+use bevy::ecs::reflect::ReflectComponent;
+use bevy::ecs::world::EntityRef;
+use bevy::reflect::{GetPath, ReflectMut, ReflectRef};
+use bevy::transform::TransformSystem;
+use bevy::ui::UiSystem;
+
+use crate::HTMLSceneSpawnError;
+use crate::conditional_style::apply_conditional_styles;
+
+// Approximations of the CSS timing functions
+#[derive(Debug, Clone, Copy, PartialEq, Default, Reflect)]
+pub enum Easing {
+    Linear,
+    #[default]
+    Ease,
+    EaseIn,
+    EaseOut,
+    EaseInOut
+}
+impl Easing {
+    pub fn ease(&self, t: f32) -> f32 {
+        match self {
+            Self::Linear => t,
+            Self::Ease => t * t * (3. - 2. * t),
+            Self::EaseIn => t * t * t,
+            Self::EaseOut => 1. - (1. - t).powi(3),
+            Self::EaseInOut if t < 0.5 => 4. * t * t * t,
+            Self::EaseInOut => 1. - (2. - 2. * t).powi(3) / 2.
+        }
+    }
+}
+impl FromStr for Easing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Self::Linear),
+            "ease" => Ok(Self::Ease),
+            "ease-in" => Ok(Self::EaseIn),
+            "ease-out" => Ok(Self::EaseOut),
+            "ease-in-out" => Ok(Self::EaseInOut),
+            _ => Err(format!("Unknown easing [{s}]"))
+        }
+    }
+}
+
+// A time written like CSS, e.g. "300ms" or "1.5s", in seconds
+pub(crate) fn parse_seconds(s: &str) -> Option<f32> {
+    match (s.strip_suffix("ms"), s.strip_suffix('s')) {
+        (Some(ms), _) => ms.parse::<f32>().ok().map(|ms| ms / 1000.),
+        (None, Some(s)) => s.parse::<f32>().ok(),
+        _ => None
+    }
+}
+
+// A component, or a field of it, whose changes are interpolated (times in seconds)
+#[derive(Debug, Clone, PartialEq, Reflect)]
+pub struct Transition {
+    pub component: String,
+    pub path: Option<String>, // Field path within the component, e.g. width or translation.x
+    pub duration: f32,
+    pub delay: f32,
+    pub easing: Easing
+}
+// Written like CSS, e.g. "Style.width 300ms ease-in 0.1s"
+impl FromStr for Transition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let property = parts.next().ok_or("Missing component")?;
+        let (component, path) = match property.split_once('.') {
+            Some((component, path)) => (component, Some(path.to_string())),
+            None => (property, None)
+        };
+        let mut transition = Transition { component: component.to_string(), path, duration: 0., delay: 0., easing: Easing::default() };
+
+        // The first time is the duration, the second the delay
+        let mut times = 0;
+        for part in parts {
+            match (parse_seconds(part), times) {
+                (Some(seconds), 0) => transition.duration = seconds,
+                (Some(seconds), 1) => transition.delay = seconds,
+                (Some(_), _) => return Err(format!("Too many times in [{s}]")),
+                (None, _) => { transition.easing = part.parse()?; continue; }
+            }
+            times += 1;
+        }
+        Ok(transition)
+    }
+}
+
+// Interpolates changes to component values instead of letting them jump, whether they come from
+// pseudo-classes, swaps or game code, e.g. x-transition="BackgroundColor 200ms ease-out, Style.width 300ms"
+#[derive(Component, Default, Debug, Clone, Reflect)]
+#[reflect(Component, Default)]
+pub struct XTransition(pub Vec<Transition>);
+impl FromStr for XTransition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|transition| !transition.is_empty())
+            .map(Transition::from_str)
+            .collect::<Result<_, _>>()
+            .map(XTransition)
+    }
+}
+
+pub(crate) fn parse_x_transition(value: &str) -> Result<XTransition, HTMLSceneSpawnError> {
+    value.parse().map_err(|error| HTMLSceneSpawnError::InvalidValue("x-transition".to_string(), error))
+}
+
+// Sets `value` to the leaf type T interpolated between `from` and `to`, if it's a T
+fn lerp_leaf<T: FromReflect>(value: &mut dyn Reflect, from: &dyn Reflect, to: &dyn Reflect, lerp: impl Fn(T, T) -> T) -> bool {
+    if !value.is::<T>() {
+        return false;
+    }
+    match (T::from_reflect(from), T::from_reflect(to)) {
+        (Some(from), Some(to)) => value.apply(&lerp(from, to)),
+        _ => value.apply(to)
+    }
+    true
+}
+
+// Interpolates `value` from `from` to `to` field by field, jumping to `to` wherever that's not possible,
+// e.g. between different Val variants
+fn lerp_reflect(value: &mut dyn Reflect, from: &dyn Reflect, to: &dyn Reflect, t: f32) {
+    if lerp_leaf::<f32>(value, from, to, |a, b| a + (b - a) * t)
+        || lerp_leaf::<f64>(value, from, to, |a, b| a + (b - a) * t as f64)
+        || lerp_leaf::<Quat>(value, from, to, |a, b| a.slerp(b, t))
+        || lerp_leaf::<Color>(value, from, to, |a, b| {
+            let (a, b) = (a.as_rgba_f32(), b.as_rgba_f32());
+            let c: [f32; 4] = std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t);
+            Color::rgba(c[0], c[1], c[2], c[3])
+        }) {
+        return;
+    }
+
+    value.apply(to);
+    match (value.reflect_mut(), from.reflect_ref(), to.reflect_ref()) {
+        (ReflectMut::Struct(value), ReflectRef::Struct(from), ReflectRef::Struct(to)) => {
+            for i in 0..value.field_len() {
+                let name = value.name_at(i).unwrap().to_string();
+                if let (Some(from), Some(to), Some(field)) = (from.field(&name), to.field(&name), value.field_at_mut(i)) {
+                    lerp_reflect(field, from, to, t);
+                }
+            }
+        },
+        (ReflectMut::TupleStruct(value), ReflectRef::TupleStruct(from), ReflectRef::TupleStruct(to)) => {
+            for i in 0..value.field_len() {
+                if let (Some(from), Some(to), Some(field)) = (from.field(i), to.field(i), value.field_mut(i)) {
+                    lerp_reflect(field, from, to, t);
+                }
+            }
+        },
+        (ReflectMut::Tuple(value), ReflectRef::Tuple(from), ReflectRef::Tuple(to)) => {
+            for i in 0..value.field_len() {
+                if let (Some(from), Some(to), Some(field)) = (from.field(i), to.field(i), value.field_mut(i)) {
+                    lerp_reflect(field, from, to, t);
+                }
+            }
+        },
+        (ReflectMut::Enum(value), ReflectRef::Enum(from), ReflectRef::Enum(to)) if from.variant_name() == to.variant_name() => {
+            for i in 0..value.field_len() {
+                if let (Some(from), Some(to), Some(field)) = (from.field_at(i), to.field_at(i), value.field_at_mut(i)) {
+                    lerp_reflect(field, from, to, t);
+                }
+            }
+        },
+        _ => ()
+    }
+}
+
+fn field<'a>(component: &'a dyn Reflect, path: &Option<String>) -> Option<&'a dyn Reflect> {
+    match path {
+        Some(path) => component.reflect_path(path.as_str()).ok(),
+        None => Some(component)
+    }
+}
+fn field_mut<'a>(component: &'a mut dyn Reflect, path: &Option<String>) -> Option<&'a mut dyn Reflect> {
+    match path {
+        Some(path) => component.reflect_path_mut(path.as_str()).ok(),
+        None => Some(component)
+    }
+}
+
+#[derive(Default)]
+struct TransitionState {
+    component: Option<TypeId>,
+    // Value last written by the transition, or seen while idle
+    displayed: Option<Box<dyn Reflect>>,
+    from: Option<Box<dyn Reflect>>,
+    to: Option<Box<dyn Reflect>>,
+    elapsed: f32
+}
+impl TransitionState {
+    fn step(&mut self, entity: &mut EntityWorldMut, reflect_component: &ReflectComponent, transition: &Transition, delta: f32) {
+        let Some(mut component) = reflect_component.reflect_mut(entity) else { return; };
+        // Compare without triggering change detection
+        let Some(current) = field(&*component, &transition.path) else { return; };
+        match &self.displayed {
+            // Values that can't be compared are never animated
+            Some(displayed) if displayed.reflect_partial_eq(current) != Some(false) => (),
+            Some(displayed) => {
+                // Changed by something else, animate to the new value from what's shown
+                self.from = Some(displayed.clone_value());
+                self.to = Some(current.clone_value());
+                self.elapsed = 0.;
+            },
+            // The value the component starts with isn't animated
+            None => {
+                self.displayed = Some(current.clone_value());
+                return;
+            }
+        }
+
+        let (Some(from), Some(to)) = (&self.from, &self.to) else { return; };
+        self.elapsed += delta;
+        let t = match transition.duration {
+            duration if duration > 0. => ((self.elapsed - transition.delay) / duration).clamp(0., 1.),
+            _ if self.elapsed >= transition.delay => 1.,
+            _ => 0.
+        };
+        let Some(value) = field_mut(&mut *component, &transition.path) else { return; };
+        lerp_reflect(value, &**from, &**to, transition.easing.ease(t));
+        self.displayed = Some(value.clone_value());
+        if t >= 1. {
+            self.from = None;
+            self.to = None;
+        }
+    }
+}
+
+// Transition state, kept for the transitions it was made for
+#[derive(Component, Default)]
+struct TransitionStates {
+    transitions: Vec<Transition>,
+    states: Vec<TransitionState>
+}
+
+// The component's value with transitions in progress skipped to their end, so it isn't captured midway
+pub(crate) fn settled_value(entity: EntityRef, component: TypeId, value: &dyn Reflect) -> Box<dyn Reflect> {
+    let mut value = value.clone_value();
+    let Some(states) = entity.get::<TransitionStates>().filter(|_| entity.contains::<XTransition>()) else { return value; };
+    for (transition, state) in states.transitions.iter().zip(&states.states) {
+        let Some(to) = state.to.as_ref().filter(|_| state.component == Some(component)) else { continue; };
+        if let Some(field) = field_mut(&mut *value, &transition.path) {
+            field.apply(&**to);
+        }
+    }
+    value
+}
+
+fn animate_transitions(world: &mut World) {
+    let delta = world.resource::<Time>().delta_seconds();
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+
+    // New or changed transitions start over
+    let mut query = world.query::<(Entity, &XTransition, Option<&TransitionStates>)>();
+    let outdated: Vec<(Entity, Vec<Transition>)> = query.iter(world)
+        .filter(|(_, transition, states)| states.map_or(true, |states| states.transitions != transition.0))
+        .map(|(entity, transition, _)| (entity, transition.0.clone()))
+        .collect();
+    for (entity, transitions) in outdated {
+        let states = transitions.iter()
+            .map(|transition| TransitionState {
+                component: match type_registry.get_with_short_type_path(&transition.component) {
+                    Some(registration) => Some(registration.type_id()),
+                    None => {
+                        warn!("Transition [{}]: Referred to undefined component", transition.component);
+                        None
+                    }
+                },
+                ..default()
+            })
+            .collect();
+        world.entity_mut(entity).insert(TransitionStates { transitions, states });
+    }
+
+    let mut query = world.query_filtered::<Entity, (With<XTransition>, With<TransitionStates>)>();
+    let transitioning: Vec<Entity> = query.iter(world).collect();
+    for entity in transitioning {
+        let mut entity = world.entity_mut(entity);
+        // Moved out while stepping, since the animated components are borrowed from the same entity
+        let mut states = std::mem::take(&mut *entity.get_mut::<TransitionStates>().unwrap());
+        for (transition, state) in states.transitions.iter().zip(states.states.iter_mut()) {
+            let Some(reflect_component) = state.component.and_then(|component| type_registry.get_type_data::<ReflectComponent>(component)) else { continue; };
+            state.step(&mut entity, reflect_component, transition, delta);
+        }
+        *entity.get_mut::<TransitionStates>().unwrap() = states;
+    }
+}
+
+pub struct TransitionPlugin;
+impl Plugin for TransitionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .register_type::<XTransition>()
+            .register_type::<Transition>()
+            .register_type::<Easing>()
+
+            // After anything else changes values this frame, but before they're used
+            .add_systems(PostUpdate, animate_transitions
+                .after(apply_conditional_styles)
+                .before(UiSystem::Layout)
+                .before(TransformSystem::TransformPropagate));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transitions_parse_like_css() {
+        assert_eq!("Style.width 300ms ease-in 0.1s".parse(), Ok(Transition {
+            component: "Style".to_string(), path: Some("width".to_string()), duration: 0.3, delay: 0.1, easing: Easing::EaseIn
+        }));
+        assert_eq!("BackgroundColor".parse(), Ok(Transition {
+            component: "BackgroundColor".to_string(), path: None, duration: 0., delay: 0., easing: Easing::Ease
+        }));
+    }
+
+    #[test]
+    fn invalid_transitions_are_errors() {
+        assert_eq!("Style 1s 2s 3s".parse::<Transition>(), Err("Too many times in [Style 1s 2s 3s]".to_string()));
+        assert_eq!("Style 1s bouncy".parse::<Transition>(), Err("Unknown easing [bouncy]".to_string()));
+        assert!("".parse::<Transition>().is_err());
+    }
+
+    #[test]
+    fn x_transitions_are_comma_separated() {
+        let XTransition(transitions) = "BackgroundColor 200ms ease-out, Style.width 300ms,".parse().unwrap();
+        assert_eq!(transitions.len(), 2);
+        assert_eq!(transitions[0].easing, Easing::EaseOut);
+        assert_eq!(transitions[1].path.as_deref(), Some("width"));
+    }
+
+    #[test]
+    fn easings_start_at_zero_and_end_at_one() {
+        for easing in [Easing::Linear, Easing::Ease, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut] {
+            assert_eq!(easing.ease(0.), 0., "{easing:?}");
+            assert_eq!(easing.ease(1.), 1., "{easing:?}");
+            // Monotonic in between
+            let samples: Vec<f32> = (0..=10).map(|i| easing.ease(i as f32 / 10.)).collect();
+            assert!(samples.windows(2).all(|pair| pair[0] <= pair[1]), "{easing:?}");
+        }
+    }
+
+    #[test]
+    fn easings_have_their_css_shape() {
+        assert_eq!(Easing::Linear.ease(0.25), 0.25);
+        assert!(Easing::EaseIn.ease(0.25) < 0.25);
+        assert!(Easing::EaseOut.ease(0.25) > 0.25);
+        assert_eq!(Easing::EaseInOut.ease(0.5), 0.5);
+        assert_eq!(Easing::Ease.ease(0.5), 0.5);
+    }
+}